@@ -0,0 +1,10 @@
+//! Parses arbitrary bytes and uses whatever comes out, which must not
+//! panic. Starting from the samples gets past the ELF header quickly:
+//!
+//!     cargo +nightly fuzz run parse corpus/parse ../../elk/samples
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &[u8]| delf::exercise(input));
@@ -0,0 +1,484 @@
+use crate::{parse, Addr, Class, Endianness, Error, File, Machine, Note};
+use derive_try_from_primitive::TryFromPrimitive;
+use serde::Serialize;
+use std::fmt;
+
+/// Types of the notes owned by "CORE", found in core dumps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+#[repr(u32)]
+pub enum CoreNoteType {
+    PrStatus = 1,
+    PrFpReg = 2,
+    PrPsInfo = 3,
+    TaskStruct = 4,
+    Auxv = 6,
+    SigInfo = 0x53494749,
+    File = 0x46494c45,
+}
+
+/// Entry types of the auxiliary vector, which the kernel passes to
+/// programs on their initial stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, Serialize)]
+#[repr(u64)]
+pub enum AuxType {
+    Null = 0,
+    Ignore = 1,
+    ExecFd = 2,
+    Phdr = 3,
+    PhEnt = 4,
+    PhNum = 5,
+    PageSz = 6,
+    Base = 7,
+    Flags = 8,
+    Entry = 9,
+    NotElf = 10,
+    Uid = 11,
+    EUid = 12,
+    Gid = 13,
+    EGid = 14,
+    Platform = 15,
+    HwCap = 16,
+    ClkTck = 17,
+    Secure = 23,
+    BasePlatform = 24,
+    Random = 25,
+    HwCap2 = 26,
+    RseqFeatureSize = 27,
+    RseqAlign = 28,
+    HwCap3 = 29,
+    HwCap4 = 30,
+    ExecFn = 31,
+    SysInfo = 32,
+    SysInfoEhdr = 33,
+    MinSigStkSz = 51,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AuxvEntry {
+    pub r#type: AuxType,
+    pub value: u64,
+}
+
+impl AuxvEntry {
+    /// Parses a whole auxiliary vector, up to its `Null` entry. Entries of
+    /// unknown types are skipped.
+    pub fn parse_all(ctx: parse::Context, i: parse::Input) -> parse::Result<Vec<Self>> {
+        use nom::sequence::tuple;
+
+        let mut entries = Vec::new();
+        let mut i = i;
+        loop {
+            let (rest, (r#type, value)) = tuple((ctx.word(), ctx.word()))(i)?;
+            i = rest;
+            match AuxType::try_from(r#type) {
+                Ok(AuxType::Null) => return Ok((i, entries)),
+                Ok(r#type) => entries.push(Self { r#type, value }),
+                Err(_) => continue,
+            }
+        }
+    }
+
+    /// Decodes an auxiliary vector outside of a core dump, such as the one
+    /// in `/proc/self/auxv`
+    pub fn from_bytes(class: Class, endianness: Endianness, i: &[u8]) -> Result<Vec<Self>, Error> {
+        Self::parse_all(parse::Context { class, endianness }, i)
+            .map(|(_, entries)| entries)
+            .map_err(|err| Error::from_nom(i, err))
+    }
+}
+
+/// General purpose registers, in the order of `struct user_regs_struct`
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct X86_64Registers {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rax: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    /// System call number, if stopped in one
+    pub orig_rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub eflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+    pub fs_base: u64,
+    pub gs_base: u64,
+    pub ds: u64,
+    pub es: u64,
+    pub fs: u64,
+    pub gs: u64,
+}
+
+impl X86_64Registers {
+    const COUNT: usize = 27;
+
+    pub fn parse(ctx: parse::Context, i: parse::Input) -> parse::Result<Self> {
+        use nom::multi::count;
+
+        let (i, r) = count(ctx.u64(), Self::COUNT)(i)?;
+        let res = Self {
+            r15: r[0],
+            r14: r[1],
+            r13: r[2],
+            r12: r[3],
+            rbp: r[4],
+            rbx: r[5],
+            r11: r[6],
+            r10: r[7],
+            r9: r[8],
+            r8: r[9],
+            rax: r[10],
+            rcx: r[11],
+            rdx: r[12],
+            rsi: r[13],
+            rdi: r[14],
+            orig_rax: r[15],
+            rip: r[16],
+            cs: r[17],
+            eflags: r[18],
+            rsp: r[19],
+            ss: r[20],
+            fs_base: r[21],
+            gs_base: r[22],
+            ds: r[23],
+            es: r[24],
+            fs: r[25],
+            gs: r[26],
+        };
+        Ok((i, res))
+    }
+}
+
+impl fmt::Debug for X86_64Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let general = [
+            ("rax", self.rax),
+            ("rbx", self.rbx),
+            ("rcx", self.rcx),
+            ("rdx", self.rdx),
+            ("rsi", self.rsi),
+            ("rdi", self.rdi),
+            ("rbp", self.rbp),
+            ("rsp", self.rsp),
+            ("r8", self.r8),
+            ("r9", self.r9),
+            ("r10", self.r10),
+            ("r11", self.r11),
+            ("r12", self.r12),
+            ("r13", self.r13),
+            ("r14", self.r14),
+            ("r15", self.r15),
+            ("rip", self.rip),
+            ("eflags", self.eflags),
+            ("fs_base", self.fs_base),
+        ];
+        for (i, (name, value)) in general.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}={:#x}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Status of a thread (`struct elf_prstatus`). Only the x86-64 layout is
+/// decoded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PrStatus {
+    pub signal: u32,
+    /// The signal the thread was handling when the dump was taken
+    pub current_signal: u16,
+    pub pending_signals: u64,
+    pub held_signals: u64,
+    pub pid: u32,
+    pub ppid: u32,
+    pub pgrp: u32,
+    pub sid: u32,
+    pub registers: X86_64Registers,
+}
+
+impl PrStatus {
+    const X86_64_SIZE: usize = 336;
+
+    pub fn parse(ctx: parse::Context, i: parse::Input) -> parse::Result<Self> {
+        use nom::{bytes::complete::take, sequence::tuple};
+
+        let (i, (signal, _code, _errno)) = tuple((ctx.u32(), ctx.u32(), ctx.u32()))(i)?;
+        let (i, (current_signal, _)) = tuple((ctx.u16(), take(2_usize)))(i)?;
+        let (i, (pending_signals, held_signals)) = tuple((ctx.u64(), ctx.u64()))(i)?;
+        let (i, (pid, ppid, pgrp, sid)) = tuple((ctx.u32(), ctx.u32(), ctx.u32(), ctx.u32()))(i)?;
+        // user and system time, of the thread and of its children
+        let (i, _) = take(64_usize)(i)?;
+        let (i, registers) = X86_64Registers::parse(ctx, i)?;
+        // whether floating point registers were dumped, and padding
+        let (i, _) = take(8_usize)(i)?;
+
+        let res = Self {
+            signal,
+            current_signal,
+            pending_signals,
+            held_signals,
+            pid,
+            ppid,
+            pgrp,
+            sid,
+            registers,
+        };
+        Ok((i, res))
+    }
+}
+
+/// Information about the process (`struct elf_prpsinfo`). Only the 64-bit
+/// layout is decoded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PrPsInfo {
+    pub state: u8,
+    /// State as shown by `ps`, e.g. 'R' for running
+    pub state_name: char,
+    pub zombie: bool,
+    pub nice: i8,
+    pub flags: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: u32,
+    pub ppid: u32,
+    pub pgrp: u32,
+    pub sid: u32,
+    /// Name of the executable, truncated to 15 bytes
+    pub filename: String,
+    /// Command line, truncated to 79 bytes
+    pub args: String,
+}
+
+impl PrPsInfo {
+    const ELF64_SIZE: usize = 136;
+
+    pub fn parse(ctx: parse::Context, i: parse::Input) -> parse::Result<Self> {
+        use nom::{bytes::complete::take, number::complete::u8, sequence::tuple};
+
+        let (i, (state, state_name, zombie, nice, _)) = tuple((u8, u8, u8, u8, take(4_usize)))(i)?;
+        let (i, (flags, uid, gid)) = tuple((ctx.u64(), ctx.u32(), ctx.u32()))(i)?;
+        let (i, (pid, ppid, pgrp, sid)) = tuple((ctx.u32(), ctx.u32(), ctx.u32(), ctx.u32()))(i)?;
+        let (i, (filename, args)) = tuple((take(16_usize), take(80_usize)))(i)?;
+
+        let res = Self {
+            state,
+            state_name: state_name as char,
+            zombie: zombie != 0,
+            nice: nice as i8,
+            flags,
+            uid,
+            gid,
+            pid,
+            ppid,
+            pgrp,
+            sid,
+            filename: c_string(filename),
+            args: c_string(args).trim_end().to_owned(),
+        };
+        Ok((i, res))
+    }
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+/// A memory region backed by a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MappedFile {
+    pub start: Addr,
+    pub end: Addr,
+    /// Offset in the file, in bytes
+    pub offset: Addr,
+    pub path: String,
+}
+
+impl MappedFile {
+    /// Parses a whole `NT_FILE` note
+    pub fn parse_all(ctx: parse::Context, i: parse::Input) -> parse::Result<Vec<Self>> {
+        use nom::{
+            combinator::verify,
+            multi::count,
+            sequence::{pair, tuple},
+        };
+
+        let word_size = match ctx.class {
+            Class::Elf32 => 4,
+            Class::Elf64 => 8,
+        };
+        // `count` makes room for all the ranges up front, so there had
+        // better be that many
+        let (i, (n, page_size)) = pair(
+            verify(ctx.word(), |&n| n <= (i.len() / (3 * word_size)) as u64),
+            ctx.word(),
+        )(i)?;
+        let (i, ranges) = count(tuple((ctx.word(), ctx.word(), ctx.word())), n as usize)(i)?;
+
+        // the paths follow all the ranges, null-terminated
+        let paths = i.split(|&b| b == 0).map(c_string);
+        let res = ranges
+            .into_iter()
+            .zip(paths)
+            .map(|((start, end, page), path)| Self {
+                start: Addr(start),
+                end: Addr(end),
+                offset: Addr(page.wrapping_mul(page_size)),
+                path,
+            })
+            .collect();
+        Ok((&[], res))
+    }
+}
+
+/// Decodes a "CORE" note, if its layout is known: thread statuses hold
+/// registers, so only x86-64 ones are
+pub(crate) fn decode_note(
+    ctx: parse::Context,
+    machine: Machine,
+    r#type: u32,
+    desc: parse::Input,
+) -> Option<Note> {
+    let elf64 = ctx.class == Class::Elf64;
+    let x86_64 = machine == Machine::X86_64;
+    let res = match CoreNoteType::try_from(r#type).ok()? {
+        CoreNoteType::PrStatus if x86_64 && elf64 && desc.len() == PrStatus::X86_64_SIZE => {
+            Note::CorePrStatus(PrStatus::parse(ctx, desc).ok()?.1)
+        }
+        CoreNoteType::PrPsInfo if elf64 && desc.len() == PrPsInfo::ELF64_SIZE => {
+            Note::CorePrPsInfo(PrPsInfo::parse(ctx, desc).ok()?.1)
+        }
+        CoreNoteType::Auxv => Note::CoreAuxv(AuxvEntry::parse_all(ctx, desc).ok()?.1),
+        CoreNoteType::File => Note::CoreFile(MappedFile::parse_all(ctx, desc).ok()?.1),
+        _ => return None,
+    };
+    Some(res)
+}
+
+impl File {
+    /// Status of every thread in a core dump, starting with the one that
+    /// caused the dump. Only x86-64 core dumps have any: other machines'
+    /// statuses are left as `Note::Other`.
+    pub fn threads(&self) -> Vec<PrStatus> {
+        self.notes()
+            .into_iter()
+            .filter_map(|note| match note {
+                Note::CorePrStatus(status) => Some(status),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The signal that caused a core dump
+    pub fn crash_signal(&self) -> Option<u16> {
+        self.threads().first().map(|t| t.current_signal)
+    }
+
+    pub fn process_info(&self) -> Option<PrPsInfo> {
+        self.notes().into_iter().find_map(|note| match note {
+            Note::CorePrPsInfo(info) => Some(info),
+            _ => None,
+        })
+    }
+
+    /// Auxiliary vector of the process a core dump was taken from
+    pub fn auxv(&self) -> Vec<AuxvEntry> {
+        self.notes()
+            .into_iter()
+            .find_map(|note| match note {
+                Note::CoreAuxv(entries) => Some(entries),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// File-backed memory regions of the process a core dump was taken from
+    pub fn mapped_files(&self) -> Vec<MappedFile> {
+        self.notes()
+            .into_iter()
+            .find_map(|note| match note {
+                Note::CoreFile(files) => Some(files),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// The file-backed region an address of a core dump falls in
+    pub fn mapped_file_at(&self, addr: Addr) -> Option<MappedFile> {
+        self.mapped_files()
+            .into_iter()
+            .find(|f| (f.start..f.end).contains(&addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuxType, AuxvEntry};
+    use crate::{Addr, Class, Endianness, File, Machine, Note, Type};
+
+    #[test]
+    fn core_dump() {
+        let input = include_bytes!("../../elk/samples/crash.core");
+        let file = File::from_bytes(&input[..]).unwrap();
+        assert_eq!(file.r#type, Type::Core);
+
+        // SIGSEGV on `movq %rax, 0`, right after `movq $42, %rax`
+        assert_eq!(file.crash_signal(), Some(11));
+        let threads = file.threads();
+        assert_eq!(threads.len(), 1);
+        assert_eq!(threads[0].registers.rax, 42);
+        assert_eq!(threads[0].registers.rip, 0x401007);
+
+        let info = file.process_info().unwrap();
+        assert_eq!(info.filename, "crash");
+        assert_eq!(info.args, "./crash arg");
+        assert_eq!(info.pid, threads[0].pid);
+
+        let auxv = file.auxv();
+        assert!(auxv.contains(&AuxvEntry {
+            r#type: AuxType::Entry,
+            value: 0x401000
+        }));
+        assert!(auxv.contains(&AuxvEntry {
+            r#type: AuxType::PageSz,
+            value: 0x1000
+        }));
+
+        let host = std::fs::read("/proc/self/auxv").unwrap();
+        let host = AuxvEntry::from_bytes(Class::Elf64, Endianness::Little, &host).unwrap();
+        assert!(host.iter().any(|e| e.r#type == AuxType::Phdr));
+
+        let text = file.mapped_file_at(Addr(0x401007)).unwrap();
+        assert!(text.path.ends_with("/crash"), "{}", text.path);
+        assert_eq!(text.offset, Addr(0x1000));
+        assert!(file.mapped_file_at(Addr(0)).is_none());
+    }
+
+    #[test]
+    fn other_machine_core_dump() {
+        let mut input = include_bytes!("../../elk/samples/crash.core").to_vec();
+        // e_machine: AArch64, whose registers are laid out differently
+        input[18..20].copy_from_slice(&u16::from(Machine::AArch64).to_le_bytes());
+
+        let file = File::from_bytes(&input).unwrap();
+        assert!(file.threads().is_empty());
+        assert!(file.notes().iter().any(|note| matches!(
+            note,
+            Note::Other { name, r#type: 1, desc } if name == "CORE" && desc.len() == 336
+        )));
+        assert!(file.process_info().is_some());
+    }
+}
@@ -0,0 +1,593 @@
+//! Line number programs of `.debug_line` (DWARF 2 to 5), which map
+//! addresses to the source lines they were compiled from
+
+use crate::{parse, Addr, Error, File, SectionFlag};
+use nom::{
+    bytes::complete::{tag, take, take_till},
+    combinator::map,
+    multi::many_till,
+    number::complete::{i8, u8},
+    sequence::terminated,
+};
+use std::path::{Path, PathBuf};
+
+// standard opcodes
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_SET_FILE: u8 = 4;
+const DW_LNS_CONST_ADD_PC: u8 = 8;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 9;
+
+// extended opcodes
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+const DW_LNE_DEFINE_FILE: u8 = 3;
+
+// what the columns of DWARF 5 directory and file name tables hold
+const DW_LNCT_PATH: u64 = 1;
+const DW_LNCT_DIRECTORY_INDEX: u64 = 2;
+
+// how they are encoded
+const DW_FORM_BLOCK2: u64 = 0x03;
+const DW_FORM_BLOCK4: u64 = 0x04;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_DATA8: u64 = 0x07;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_BLOCK: u64 = 0x09;
+const DW_FORM_BLOCK1: u64 = 0x0a;
+const DW_FORM_DATA1: u64 = 0x0b;
+const DW_FORM_SDATA: u64 = 0x0d;
+const DW_FORM_STRP: u64 = 0x0e;
+const DW_FORM_UDATA: u64 = 0x0f;
+const DW_FORM_DATA16: u64 = 0x1e;
+const DW_FORM_LINE_STRP: u64 = 0x1f;
+
+/// A row of the line number matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Row {
+    addr: Addr,
+    /// Index in `LineTable::files`, if the program named a valid file
+    file: Option<usize>,
+    line: u32,
+    /// The first address after a sequence of rows, rather than the start
+    /// of a line
+    end_sequence: bool,
+}
+
+/// The line number matrices of all compilation units, sorted by address, to
+/// find the source lines addresses belong to
+#[derive(Debug, Default)]
+pub struct LineTable {
+    rows: Vec<Row>,
+    files: Vec<PathBuf>,
+}
+
+impl LineTable {
+    /// Finds the source file and line an address was compiled from: that of
+    /// the closest preceding row, unless a sequence ends in between. Rows of
+    /// line 0 are for code compiled from no line in particular, such as
+    /// what the compiler generates: like `addr2line`, they give nothing.
+    ///
+    /// Files of DWARF 4 (and earlier) units that are in the compilation
+    /// directory come without a directory, as only `.debug_info` records it.
+    pub fn lookup(&self, addr: Addr) -> Option<(&Path, u32)> {
+        let end = self.rows.partition_point(|row| row.addr <= addr);
+        let row = self.rows[..end].last()?;
+        if row.end_sequence || row.line == 0 {
+            return None;
+        }
+        Some((&self.files[row.file?], row.line))
+    }
+
+    /// Decodes the line number programs of all units of a `.debug_line`
+    /// section, reading strings from `.debug_line_str` and `.debug_str`
+    pub fn parse(
+        ctx: parse::Context,
+        debug_line: &[u8],
+        line_str: &[u8],
+        str: &[u8],
+    ) -> Result<Self, Error> {
+        let mut table = Self::default();
+        let mut i = debug_line;
+        while !i.is_empty() {
+            let offset = debug_line.len() - i.len();
+            let strings = Strings { line_str, str };
+            i = table
+                .parse_unit(ctx, strings, i)
+                .map_err(|_| Error::InvalidLineProgram(offset))?
+                .0;
+        }
+        // where a sequence ends right where another starts, the end goes
+        // first, so that lookups find the start
+        table.rows.sort_by_key(|row| (row.addr, !row.end_sequence));
+        Ok(table)
+    }
+
+    /// Parses a unit's header and runs its line number program, adding its
+    /// files and rows to the table
+    fn parse_unit<'a>(
+        &mut self,
+        ctx: parse::Context,
+        strings: Strings,
+        i: parse::Input<'a>,
+    ) -> parse::Result<'a, ()> {
+        let (i, (offset_size, unit_length)) = unit_length(ctx, i)?;
+        let (rest, unit) = take(unit_length)(i)?;
+
+        let (i, version) = ctx.u16()(unit)?;
+        if !(2..=5).contains(&version) {
+            return Err(invalid(unit));
+        }
+        let i = if version >= 5 {
+            // address and segment selector sizes, known from DW_LNE_set_address
+            take(2_usize)(i)?.0
+        } else {
+            i
+        };
+        let (i, header_length) = offset(ctx, offset_size)(i)?;
+        let (program, header) = take(header_length)(i)?;
+
+        let (i, min_inst_length) = u8(header)?;
+        let (i, max_ops_per_inst) = if version >= 4 { u8(i)? } else { (i, 1) };
+        // default_is_stmt: all rows matter for lookups
+        let (i, _) = u8(i)?;
+        let (i, line_base) = i8(i)?;
+        let (i, line_range) = u8(i)?;
+        let (i, opcode_base) = u8(i)?;
+        let (i, standard_opcode_lengths) = take(opcode_base.saturating_sub(1))(i)?;
+        let params = Params {
+            min_inst_length: min_inst_length.into(),
+            max_ops_per_inst: max_ops_per_inst.max(1).into(),
+            line_base: line_base.into(),
+            line_range: line_range.max(1),
+            opcode_base,
+            standard_opcode_lengths,
+        };
+
+        // indices of the unit's files in `self.files`, from the unit's own
+        // file numbers, which start at 1 before DWARF 5
+        let mut files = Vec::new();
+        if version >= 5 {
+            let (i, dirs) = entries(ctx, offset_size, strings, i)?;
+            let (_, names) = entries(ctx, offset_size, strings, i)?;
+            for (name, dir) in names {
+                let dir = dirs.get(dir as usize).map(|(dir, _)| dir.as_str());
+                files.push(Some(self.add_file(dir, &name)));
+            }
+        } else {
+            files.push(None);
+            let (i, (dirs, _)) = many_till(c_string, tag(b"\0"))(i)?;
+            let (_, (names, _)) = many_till(file_entry, tag(b"\0"))(i)?;
+            for (name, dir) in names {
+                files.push(self.add_v4_file(&dirs, dir, &name));
+            }
+        }
+
+        let mut state = State::new();
+        let mut i = program;
+        while !i.is_empty() {
+            let (rest, opcode) = u8(i)?;
+            i = rest;
+            if opcode >= params.opcode_base {
+                let adjusted = opcode - params.opcode_base;
+                state.advance(&params, (adjusted / params.line_range).into());
+                state.line = state.line.wrapping_add_signed(
+                    params.line_base + i32::from(adjusted % params.line_range),
+                );
+                self.rows.push(state.row(&files, false));
+                continue;
+            }
+            match opcode {
+                0 => {
+                    let (rest, len) = uleb128(i)?;
+                    let (rest, body) = take(len)(rest)?;
+                    i = rest;
+                    let Some((&sub_opcode, args)) = body.split_first() else {
+                        continue;
+                    };
+                    match sub_opcode {
+                        DW_LNE_END_SEQUENCE => {
+                            self.rows.push(state.row(&files, true));
+                            state = State::new();
+                        }
+                        DW_LNE_SET_ADDRESS => {
+                            state.addr = match args.len() {
+                                4 => ctx.u32()(args)?.1.into(),
+                                8 => ctx.u64()(args)?.1,
+                                _ => return Err(invalid(args)),
+                            };
+                            state.op_index = 0;
+                        }
+                        DW_LNE_DEFINE_FILE => {
+                            let (_, (name, dir)) = file_entry(args)?;
+                            files.push(self.add_v4_file(&[], dir, &name));
+                        }
+                        // DW_LNE_set_discriminator, and vendor extensions
+                        _ => {}
+                    }
+                }
+                DW_LNS_COPY => self.rows.push(state.row(&files, false)),
+                DW_LNS_ADVANCE_PC => {
+                    let (rest, n) = uleb128(i)?;
+                    i = rest;
+                    state.advance(&params, n);
+                }
+                DW_LNS_ADVANCE_LINE => {
+                    let (rest, n) = sleb128(i)?;
+                    i = rest;
+                    state.line = state.line.wrapping_add_signed(n as i32);
+                }
+                DW_LNS_SET_FILE => {
+                    let (rest, n) = uleb128(i)?;
+                    i = rest;
+                    state.file = n;
+                }
+                DW_LNS_CONST_ADD_PC => {
+                    let adjusted = 255 - params.opcode_base;
+                    state.advance(&params, (adjusted / params.line_range).into());
+                }
+                DW_LNS_FIXED_ADVANCE_PC => {
+                    let (rest, n) = ctx.u16()(i)?;
+                    i = rest;
+                    state.addr = state.addr.wrapping_add(n.into());
+                    state.op_index = 0;
+                }
+                // the others only set registers that don't matter for
+                // lookups, skip their arguments
+                _ => {
+                    let n = params.standard_opcode_lengths[usize::from(opcode) - 1];
+                    for _ in 0..n {
+                        i = uleb128(i)?.0;
+                    }
+                }
+            }
+        }
+        Ok((rest, ()))
+    }
+
+    fn add_file(&mut self, dir: Option<&str>, name: &str) -> usize {
+        let path = match dir {
+            Some(dir) => Path::new(dir).join(name),
+            None => PathBuf::from(name),
+        };
+        self.files.push(path);
+        self.files.len() - 1
+    }
+
+    /// Adds a file of a DWARF 4 (or earlier) unit, whose directory 0 is the
+    /// compilation directory and directory `n` is `dirs[n - 1]`
+    fn add_v4_file(&mut self, dirs: &[String], dir: u64, name: &str) -> Option<usize> {
+        match dir {
+            0 => Some(self.add_file(None, name)),
+            n => {
+                let dir = dirs.get(n as usize - 1)?;
+                Some(self.add_file(Some(dir), name))
+            }
+        }
+    }
+}
+
+/// Header fields of a unit that its line number program depends on
+struct Params<'a> {
+    min_inst_length: u64,
+    max_ops_per_inst: u64,
+    line_base: i32,
+    line_range: u8,
+    opcode_base: u8,
+    /// Numbers of arguments of the standard opcodes, from 1
+    standard_opcode_lengths: &'a [u8],
+}
+
+/// Registers of the line number state machine
+struct State {
+    addr: u64,
+    op_index: u64,
+    file: u64,
+    line: u32,
+}
+
+impl State {
+    fn new() -> Self {
+        Self {
+            addr: 0,
+            op_index: 0,
+            file: 1,
+            line: 1,
+        }
+    }
+
+    /// Advances by `n` operations, which are whole instructions except on
+    /// VLIW architectures
+    fn advance(&mut self, params: &Params, n: u64) {
+        let ops = self.op_index.wrapping_add(n);
+        self.addr = self.addr.wrapping_add(
+            params
+                .min_inst_length
+                .wrapping_mul(ops / params.max_ops_per_inst),
+        );
+        self.op_index = ops % params.max_ops_per_inst;
+    }
+
+    fn row(&self, files: &[Option<usize>], end_sequence: bool) -> Row {
+        Row {
+            addr: Addr(self.addr),
+            file: files.get(self.file as usize).copied().flatten(),
+            line: self.line,
+            end_sequence,
+        }
+    }
+}
+
+/// String sections that DWARF 5 file tables may point into
+#[derive(Clone, Copy)]
+struct Strings<'a> {
+    line_str: &'a [u8],
+    str: &'a [u8],
+}
+
+/// Reads a unit length, which also tells whether offsets in the unit take 4
+/// bytes (32-bit DWARF) or 8 (64-bit DWARF)
+fn unit_length(ctx: parse::Context, i: parse::Input) -> parse::Result<(usize, usize)> {
+    let (i, len) = ctx.u32()(i)?;
+    if len == 0xffff_ffff {
+        let (i, len) = ctx.u64()(i)?;
+        Ok((i, (8, len as usize)))
+    } else {
+        Ok((i, (4, len as usize)))
+    }
+}
+
+fn offset<'a>(
+    ctx: parse::Context,
+    offset_size: usize,
+) -> impl Fn(parse::Input<'a>) -> parse::Result<'a, usize> {
+    move |i| match offset_size {
+        4 => map(ctx.u32(), |x| x as usize)(i),
+        _ => map(ctx.u64(), |x| x as usize)(i),
+    }
+}
+
+fn invalid(i: parse::Input) -> nom::Err<parse::Error> {
+    nom::Err::Failure(nom::error::make_error(i, nom::error::ErrorKind::Verify))
+}
+
+fn c_string(i: parse::Input) -> parse::Result<String> {
+    let (i, bytes) = terminated(take_till(|b| b == 0), tag(b"\0"))(i)?;
+    Ok((i, String::from_utf8_lossy(bytes).into_owned()))
+}
+
+/// A file entry before DWARF 5: name, directory index, modification time
+/// and size, of which the last two are left out
+fn file_entry(i: parse::Input) -> parse::Result<(String, u64)> {
+    let (i, name) = c_string(i)?;
+    let (i, dir) = uleb128(i)?;
+    let (i, _mtime) = uleb128(i)?;
+    let (i, _size) = uleb128(i)?;
+    Ok((i, (name, dir)))
+}
+
+/// A DWARF 5 directory or file name table: the format of its entries, then
+/// the entries. Returns the path and directory index of each.
+fn entries<'a>(
+    ctx: parse::Context,
+    offset_size: usize,
+    strings: Strings,
+    i: parse::Input<'a>,
+) -> parse::Result<'a, Vec<(String, u64)>> {
+    let (mut i, format_count) = u8(i)?;
+    let mut format = Vec::new();
+    for _ in 0..format_count {
+        let (rest, content_type) = uleb128(i)?;
+        let (rest, form) = uleb128(rest)?;
+        format.push((content_type, form));
+        i = rest;
+    }
+
+    let (mut i, count) = uleb128(i)?;
+    let mut res = Vec::new();
+    for _ in 0..count {
+        let mut entry = (String::new(), 0);
+        for &(content_type, form) in &format {
+            let (rest, value) = attribute(ctx, offset_size, strings, form, i)?;
+            i = rest;
+            match (content_type, value) {
+                (DW_LNCT_PATH, Value::String(path)) => entry.0 = path,
+                (DW_LNCT_DIRECTORY_INDEX, Value::Number(dir)) => entry.1 = dir,
+                // timestamps, sizes, MD5 digests and vendor extensions
+                _ => {}
+            }
+        }
+        res.push(entry);
+    }
+    Ok((i, res))
+}
+
+enum Value {
+    String(String),
+    Number(u64),
+    Other,
+}
+
+fn attribute<'a>(
+    ctx: parse::Context,
+    offset_size: usize,
+    strings: Strings,
+    form: u64,
+    i: parse::Input<'a>,
+) -> parse::Result<'a, Value> {
+    let string_at = |section: &[u8], offset: usize| {
+        let bytes = section.get(offset..)?;
+        c_string(bytes).ok().map(|(_, s)| Value::String(s))
+    };
+    let block = |i, len: u64| map(take(len), |_| Value::Other)(i);
+    match form {
+        DW_FORM_STRING => map(c_string, Value::String)(i),
+        DW_FORM_LINE_STRP => {
+            let (rest, offset) = offset(ctx, offset_size)(i)?;
+            Ok((
+                rest,
+                string_at(strings.line_str, offset).ok_or_else(|| invalid(i))?,
+            ))
+        }
+        DW_FORM_STRP => {
+            let (rest, offset) = offset(ctx, offset_size)(i)?;
+            Ok((
+                rest,
+                string_at(strings.str, offset).ok_or_else(|| invalid(i))?,
+            ))
+        }
+        DW_FORM_UDATA => map(uleb128, Value::Number)(i),
+        DW_FORM_SDATA => map(sleb128, |x| Value::Number(x as u64))(i),
+        DW_FORM_DATA1 => map(u8, |x| Value::Number(x.into()))(i),
+        DW_FORM_DATA2 => map(ctx.u16(), |x| Value::Number(x.into()))(i),
+        DW_FORM_DATA4 => map(ctx.u32(), |x| Value::Number(x.into()))(i),
+        DW_FORM_DATA8 => map(ctx.u64(), Value::Number)(i),
+        DW_FORM_DATA16 => block(i, 16),
+        DW_FORM_BLOCK1 => u8(i).and_then(|(i, len)| block(i, len.into())),
+        DW_FORM_BLOCK2 => ctx.u16()(i).and_then(|(i, len)| block(i, len.into())),
+        DW_FORM_BLOCK4 => ctx.u32()(i).and_then(|(i, len)| block(i, len.into())),
+        DW_FORM_BLOCK => uleb128(i).and_then(|(i, len)| block(i, len)),
+        // DW_FORM_strx and such need `.debug_str_offsets`, and the unit's
+        // entry in `.debug_info` to know where it starts
+        _ => Err(invalid(i)),
+    }
+}
+
+fn uleb128(i: parse::Input) -> parse::Result<u64> {
+    let mut res = 0_u64;
+    let mut shift = 0;
+    let mut i = i;
+    loop {
+        let (rest, byte) = u8(i)?;
+        i = rest;
+        if shift < 64 {
+            res |= u64::from(byte & 0x7f) << shift;
+        }
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok((i, res));
+        }
+    }
+}
+
+fn sleb128(i: parse::Input) -> parse::Result<i64> {
+    let mut res = 0_i64;
+    let mut shift = 0;
+    let mut i = i;
+    loop {
+        let (rest, byte) = u8(i)?;
+        i = rest;
+        if shift < 64 {
+            res |= i64::from(byte & 0x7f) << shift;
+        }
+        shift += 7;
+        if byte & 0x80 == 0 {
+            if shift < 64 && byte & 0x40 != 0 {
+                res |= -1 << shift;
+            }
+            return Ok((i, res));
+        }
+    }
+}
+
+impl File {
+    /// Decodes `.debug_line`, if any. Compressed sections are not supported.
+    pub fn line_table(&self) -> Result<LineTable, Error> {
+        let data = |name| match self.section_by_name(name) {
+            Some(sh) if sh.flags.contains(SectionFlag::Compressed) => {
+                Err(Error::CompressedSection(name.into()))
+            }
+            Some(sh) => Ok(&sh.data[..]),
+            None => Ok(&[][..]),
+        };
+        LineTable::parse(
+            self.context(),
+            data(".debug_line")?,
+            data(".debug_line_str")?,
+            data(".debug_str")?,
+        )
+    }
+
+    /// Finds the source file and line an address was compiled from. See
+    /// `LineTable::lookup`, which is faster for many lookups.
+    pub fn addr_to_line(&self, addr: Addr) -> Option<(PathBuf, u32)> {
+        self.line_table()
+            .ok()?
+            .lookup(addr)
+            .map(|(path, line)| (path.to_owned(), line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sleb128, uleb128, LineTable, Row};
+    use crate::{Addr, File};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn leb128() {
+        assert_eq!(uleb128(&[0xe5, 0x8e, 0x26]).unwrap().1, 624485);
+        assert_eq!(sleb128(&[0xc0, 0xbb, 0x78]).unwrap().1, -123456);
+        assert_eq!(sleb128(&[0x7f]).unwrap().1, -1);
+        assert!(uleb128(&[0x80]).is_err());
+    }
+
+    #[test]
+    fn dwarf_5_and_4() {
+        let input = include_bytes!("../../elk/samples/lines");
+        let file = File::from_bytes(&input[..]).unwrap();
+        let table = file.line_table().unwrap();
+        let lookup = |addr| table.lookup(Addr(addr));
+
+        // lines.c, DWARF 5: the directory comes from the unit's own table
+        assert_eq!(lookup(0x401000), Some((Path::new("./lines.c"), 3)));
+        assert_eq!(lookup(0x401016), Some((Path::new("./lines.c"), 5)));
+        // answer.c, DWARF 4, starts right where lines.c ends
+        assert_eq!(lookup(0x401024), Some((Path::new("answer.c"), 1)));
+        assert_eq!(lookup(0x40103c), Some((Path::new("answer.c"), 4)));
+        assert_eq!(lookup(0x40103d), None);
+        assert_eq!(lookup(0x400fff), None);
+
+        assert_eq!(
+            file.addr_to_line(Addr(0x40102b)),
+            Some(("answer.c".into(), 2))
+        );
+    }
+
+    #[test]
+    fn line_zero() {
+        let row = |addr, line| Row {
+            addr: Addr(addr),
+            file: Some(0),
+            line,
+            end_sequence: false,
+        };
+        let table = LineTable {
+            rows: vec![row(0x1000, 3), row(0x1008, 0), row(0x1010, 4)],
+            files: vec![PathBuf::from("main.c")],
+        };
+        assert_eq!(table.lookup(Addr(0x1004)), Some((Path::new("main.c"), 3)));
+        assert_eq!(table.lookup(Addr(0x100c)), None);
+        assert_eq!(table.lookup(Addr(0x1010)), Some((Path::new("main.c"), 4)));
+    }
+
+    #[test]
+    fn dwarf_2_include_directories() {
+        let input = include_bytes!("../../elk/samples/entrypoint");
+        let file = File::from_bytes(&input[..]).unwrap();
+        assert_eq!(
+            file.addr_to_line(Addr(0x401079)),
+            Some(("../sysdeps/x86_64/start.S".into(), 90))
+        );
+        assert_eq!(file.addr_to_line(Addr(0x40109b)), None);
+    }
+
+    #[test]
+    fn no_debug_info() {
+        let input = include_bytes!("../../elk/samples/hello");
+        let file = File::from_bytes(&input[..]).unwrap();
+        assert_eq!(file.addr_to_line(file.entry_point), None);
+    }
+}
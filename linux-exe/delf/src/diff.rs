@@ -0,0 +1,479 @@
+//! Differences between two versions of a file: what was added, removed,
+//! resized or given other flags, to explain why a binary grew
+
+use crate::{ser, Addr, File, SectionFlag, SectionType, SegmentFlag, SegmentType, SymType};
+use enumflags2::BitFlags;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::Hash,
+};
+
+/// What became of something found in either file: added if there is no
+/// `old`, removed if there is no `new`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change<K, T> {
+    pub key: K,
+    pub old: Option<T>,
+    pub new: Option<T>,
+}
+
+/// Segments have no names: they are told apart by type, then by order
+/// among those of the same type (the first `Load` segment is `(Load, 0)`)
+pub type SegmentChange = Change<(SegmentType, usize), SegmentSummary>;
+pub type SectionChange = Change<String, SectionSummary>;
+/// Symbols are told apart by name and section: file-local ones from
+/// different compile units can share a name
+pub type SymbolChange = Change<(String, String), SymbolSummary>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SegmentSummary {
+    #[serde(serialize_with = "ser::flags")]
+    pub flags: BitFlags<SegmentFlag>,
+    pub vaddr: Addr,
+    pub filesz: u64,
+    pub memsz: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SectionSummary {
+    pub r#type: SectionType,
+    #[serde(serialize_with = "ser::flags")]
+    pub flags: BitFlags<SectionFlag>,
+    pub addr: Addr,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SymbolSummary {
+    pub r#type: SymType,
+    pub value: Addr,
+    pub size: u64,
+}
+
+/// Size of whatever a change is about, for `Change::size_delta`
+pub trait Size {
+    fn size(&self) -> u64;
+}
+
+impl Size for SegmentSummary {
+    fn size(&self) -> u64 {
+        self.memsz
+    }
+}
+
+impl Size for SectionSummary {
+    fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl Size for SymbolSummary {
+    fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl<K, T: Size> Change<K, T> {
+    /// How many bytes were gained (or lost, if negative), counting what was
+    /// added or removed whole
+    pub fn size_delta(&self) -> i64 {
+        let size = |x: &Option<T>| x.as_ref().map_or(0, |x| x.size() as i64);
+        size(&self.new) - size(&self.old)
+    }
+}
+
+/// Everything that differs between two files, in the order of the new file
+/// (then of the old one, for what was removed). Addresses moving along with
+/// everything else are not changes: segments and sections only change by
+/// size, type or flags, and symbols by size or type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diff {
+    /// Old and new entry point, if it moved
+    pub entry_point: Option<(Addr, Addr)>,
+    pub segments: Vec<SegmentChange>,
+    pub sections: Vec<SectionChange>,
+    /// Sorted by how much they grew, most first
+    pub symbols: Vec<SymbolChange>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.entry_point.is_none()
+            && self.segments.is_empty()
+            && self.sections.is_empty()
+            && self.symbols.is_empty()
+    }
+}
+
+/// Pairs up the items of two files by key, keeping those for which
+/// `changed` holds and those found in only one of them. Items sharing a key
+/// are paired up in order: the first of the old file with the first of the
+/// new one, and so on.
+fn changes<K: Hash + Eq + Clone, T>(
+    old: Vec<(K, T)>,
+    new: Vec<(K, T)>,
+    changed: impl Fn(&T, &T) -> bool,
+) -> Vec<Change<K, T>> {
+    let numbered = |items: Vec<(K, T)>| {
+        let mut seen = HashMap::new();
+        items
+            .into_iter()
+            .map(|(key, item)| {
+                let n = seen.entry(key.clone()).or_insert(0);
+                *n += 1;
+                ((key, *n - 1), item)
+            })
+            .collect::<Vec<_>>()
+    };
+    let (old, new) = (numbered(old), numbered(new));
+    let mut seen = HashSet::new();
+    let keys = new
+        .iter()
+        .chain(&old)
+        .map(|(key, _)| key.clone())
+        .filter(|key| seen.insert(key.clone()))
+        .collect::<Vec<_>>();
+    let (mut old, mut new) = (
+        old.into_iter().collect::<HashMap<_, _>>(),
+        new.into_iter().collect::<HashMap<_, _>>(),
+    );
+    keys.into_iter()
+        .map(|key| Change {
+            old: old.remove(&key),
+            new: new.remove(&key),
+            key: key.0,
+        })
+        .filter(|c| match (&c.old, &c.new) {
+            (Some(old), Some(new)) => changed(old, new),
+            _ => true,
+        })
+        .collect()
+}
+
+fn segments(file: &File) -> Vec<((SegmentType, usize), SegmentSummary)> {
+    let mut seen = HashMap::new();
+    file.program_headers
+        .iter()
+        .map(|ph| {
+            let n = seen.entry(ph.r#type).or_insert(0);
+            *n += 1;
+            let summary = SegmentSummary {
+                flags: ph.flags,
+                vaddr: ph.vaddr,
+                filesz: ph.filesz.0,
+                memsz: ph.memsz.0,
+            };
+            ((ph.r#type, *n - 1), summary)
+        })
+        .collect()
+}
+
+fn sections(file: &File) -> Vec<(String, SectionSummary)> {
+    file.section_headers
+        .iter()
+        .filter(|sh| sh.r#type != SectionType::Null)
+        .map(|sh| {
+            let summary = SectionSummary {
+                r#type: sh.r#type,
+                flags: sh.flags,
+                addr: sh.addr,
+                size: sh.size.0,
+            };
+            (sh.name.clone(), summary)
+        })
+        .collect()
+}
+
+/// Named symbols defined in `.symtab`, or in `.dynsym` for stripped files,
+/// along with the name of their section (empty for absolute ones)
+fn symbols(file: &File) -> Vec<((String, String), SymbolSummary)> {
+    let table = file
+        .section_headers
+        .iter()
+        .find(|sh| sh.r#type == SectionType::SymTab)
+        .or_else(|| {
+            file.section_headers
+                .iter()
+                .find(|sh| sh.r#type == SectionType::DynSym)
+        });
+    table
+        .into_iter()
+        .flat_map(|sh| file.section_symbols(sh))
+        .filter(|sym| {
+            !sym.name.is_empty()
+                && !sym.shndx.is_undef()
+                && !matches!(sym.r#type, SymType::Section | SymType::File)
+        })
+        .map(|sym| {
+            let summary = SymbolSummary {
+                r#type: sym.r#type,
+                value: sym.value,
+                size: sym.size,
+            };
+            let section = sym
+                .shndx
+                .get()
+                .and_then(|index| file.section_headers.get(index))
+                .map(|sh| sh.name.clone())
+                .unwrap_or_default();
+            ((sym.name, section), summary)
+        })
+        .collect()
+}
+
+impl File {
+    /// Compares this file with a newer version of it
+    pub fn diff(&self, new: &File) -> Diff {
+        let mut symbols = changes(symbols(self), symbols(new), |old, new| {
+            old.size != new.size || old.r#type != new.r#type
+        });
+        // stable, so that ties keep the order of the new file
+        symbols.sort_by_key(|c| -c.size_delta());
+
+        Diff {
+            entry_point: (self.entry_point != new.entry_point)
+                .then_some((self.entry_point, new.entry_point)),
+            segments: changes(segments(self), segments(new), |old, new| {
+                old.flags != new.flags || old.filesz != new.filesz || old.memsz != new.memsz
+            }),
+            sections: changes(sections(self), sections(new), |old, new| {
+                old.r#type != new.r#type || old.flags != new.flags || old.size != new.size
+            }),
+            symbols,
+        }
+    }
+}
+
+/// `R.X` and such, as in `ProgramHeader`'s `Debug` output
+fn segment_flags(flags: BitFlags<SegmentFlag>) -> String {
+    [
+        (SegmentFlag::Read, 'R'),
+        (SegmentFlag::Write, 'W'),
+        (SegmentFlag::Execute, 'X'),
+    ]
+    .iter()
+    .map(|&(flag, letter)| if flags.contains(flag) { letter } else { '.' })
+    .collect()
+}
+
+/// `+` for added, `-` for removed, `~` for changed
+fn marker<K, T>(change: &Change<K, T>) -> char {
+    match (&change.old, &change.new) {
+        (None, _) => '+',
+        (_, None) => '-',
+        _ => '~',
+    }
+}
+
+/// `36 -> 76 bytes (+40)`, or just the size for what was added or removed
+fn sizes<K, T: Size>(change: &Change<K, T>) -> String {
+    match (&change.old, &change.new) {
+        (Some(old), Some(new)) => format!(
+            "{} -> {} bytes ({:+})",
+            old.size(),
+            new.size(),
+            change.size_delta()
+        ),
+        (Some(x), None) | (None, Some(x)) => format!("{} bytes", x.size()),
+        (None, None) => unreachable!(),
+    }
+}
+
+/// Shows `old -> new` if they differ, or whichever there is
+fn either<T: PartialEq + Copy>(
+    old: Option<T>,
+    new: Option<T>,
+    show: impl Fn(T) -> String,
+) -> String {
+    match (old, new) {
+        (Some(old), Some(new)) if old != new => format!("{} -> {}", show(old), show(new)),
+        (_, Some(x)) | (Some(x), None) => show(x),
+        (None, None) => unreachable!(),
+    }
+}
+
+fn section_flags(flags: BitFlags<SectionFlag>) -> String {
+    match flags.is_empty() {
+        true => "-".into(),
+        false => flags
+            .iter()
+            .map(|flag| format!("{:?}", flag))
+            .collect::<Vec<_>>()
+            .join("|"),
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        if let Some((old, new)) = self.entry_point {
+            writeln!(f, "Entry point: {:?} -> {:?}", old, new)?;
+        }
+
+        if !self.segments.is_empty() {
+            writeln!(f, "Segments:")?;
+        }
+        for c in &self.segments {
+            let (r#type, n) = c.key;
+            write!(
+                f,
+                "  {} {:<16} {} {}",
+                marker(c),
+                format!("{:?}[{}]", r#type, n),
+                either(
+                    c.old.map(|x| x.flags),
+                    c.new.map(|x| x.flags),
+                    segment_flags
+                ),
+                sizes(c)
+            )?;
+            // only worth a mention for segments partly outside the file,
+            // such as those with `.bss`
+            match (c.old, c.new) {
+                (Some(old), Some(new)) if (old.filesz, new.filesz) != (old.memsz, new.memsz) => {
+                    writeln!(f, ", {} -> {} in the file", old.filesz, new.filesz)?
+                }
+                _ => writeln!(f)?,
+            }
+        }
+
+        if !self.sections.is_empty() {
+            writeln!(f, "Sections:")?;
+        }
+        for c in &self.sections {
+            let show_type = |t| format!("{:?}", t);
+            writeln!(
+                f,
+                "  {} {:<20} {} {} {}",
+                marker(c),
+                c.key,
+                either(c.old.map(|x| x.r#type), c.new.map(|x| x.r#type), show_type),
+                either(
+                    c.old.map(|x| x.flags),
+                    c.new.map(|x| x.flags),
+                    section_flags
+                ),
+                sizes(c)
+            )?;
+        }
+
+        if !self.symbols.is_empty() {
+            writeln!(f, "Symbols:")?;
+        }
+        for c in &self.symbols {
+            let show_type = |t| format!("{:?}", t);
+            let (name, section) = &c.key;
+            let section = match section.as_str() {
+                "" => String::new(),
+                section => format!(" ({})", section),
+            };
+            writeln!(
+                f,
+                "  {} {}{} {} {}",
+                marker(c),
+                name,
+                section,
+                either(c.old.map(|x| x.r#type), c.new.map(|x| x.r#type), show_type),
+                sizes(c)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Addr, File, SectionType, SegmentFlag, SegmentType, SymType};
+
+    #[test]
+    fn no_differences() {
+        let input = include_bytes!("../../elk/samples/hello");
+        let file = File::from_bytes(&input[..]).unwrap();
+        let diff = file.diff(&file);
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No differences\n");
+    }
+
+    #[test]
+    fn patched() {
+        let input = include_bytes!("../../elk/samples/hello");
+        let old = File::from_bytes(&input[..]).unwrap();
+        let mut new = File::from_bytes(&input[..]).unwrap();
+        new.entry_point = Addr(0x401005);
+        new.program_headers[1].flags |= SegmentFlag::Write;
+        let text = new.section_by_name(".text").unwrap().size;
+        new.section_headers
+            .iter_mut()
+            .find(|sh| sh.name == ".text")
+            .unwrap()
+            .size = text + Addr(16);
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.entry_point, Some((old.entry_point, Addr(0x401005))));
+        assert_eq!(diff.segments.len(), 1);
+        assert_eq!(diff.segments[0].key, (SegmentType::Load, 1));
+        assert_eq!(diff.sections.len(), 1);
+        assert_eq!(diff.sections[0].key, ".text");
+        assert_eq!(diff.sections[0].size_delta(), 16);
+        assert!(diff.symbols.is_empty());
+
+        let text = diff.to_string();
+        assert!(text.contains("  ~ Load[1]          R.X -> RWX"), "{}", text);
+        assert!(text.contains(".text"), "{}", text);
+        assert!(text.contains("(+16)"), "{}", text);
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let old = include_bytes!("../../elk/samples/libgreet.so");
+        let new = include_bytes!("../../elk/samples/libinitfini.so");
+        let old = File::from_bytes(&old[..]).unwrap();
+        let new = File::from_bytes(&new[..]).unwrap();
+        let diff = old.diff(&new);
+
+        let section = |name: &str| diff.sections.iter().find(|c| c.key == name);
+        let init_array = section(".init_array").unwrap();
+        assert!(init_array.old.is_none());
+        assert_eq!(init_array.new.unwrap().r#type, SectionType::InitArray);
+        assert!(section(".data").unwrap().new.is_none());
+        assert_eq!(section(".text").unwrap().size_delta(), 10);
+
+        let symbol = |name: &str| diff.symbols.iter().find(|c| c.key.0 == name);
+        assert!(symbol("lib_init").unwrap().old.is_none());
+        assert_eq!(symbol("lib_init").unwrap().key.1, ".text");
+        assert!(diff.to_string().contains("  + lib_init (.text) NoType"));
+        let greet = symbol("greet").unwrap();
+        assert!(greet.new.is_none());
+        assert_eq!(greet.old.unwrap().r#type, SymType::Func);
+        // sorted by growth: the 8 bytes lost come last
+        assert_eq!(diff.symbols.last().unwrap().key.0, "greeting_len");
+        assert_eq!(diff.symbols.last().unwrap().size_delta(), -8);
+    }
+
+    #[test]
+    fn same_keys() {
+        use super::{changes, SymbolSummary};
+        let sym = |size| SymbolSummary {
+            r#type: SymType::Object,
+            value: Addr(0x4000),
+            size,
+        };
+        // file-local statics from two compile units
+        let key = || ("counter".to_owned(), ".bss".to_owned());
+        let changed = |old: &SymbolSummary, new: &SymbolSummary| old.size != new.size;
+
+        let old = vec![(key(), sym(4)), (key(), sym(8))];
+        let new = vec![(key(), sym(4)), (key(), sym(16))];
+        let diff = changes(old, new, changed);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].size_delta(), 8);
+
+        let diff = changes(vec![(key(), sym(4))], vec![(key(), sym(4)); 2], changed);
+        assert_eq!(diff.len(), 1);
+        assert!(diff[0].old.is_none());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{parse, Addr, DynamicTag, HexDump};
+use std::{fmt, ops::Range};
+
+/// Everything that can go wrong when reading an ELF file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The input does not start with `\x7fELF`
+    BadMagic,
+    UnsupportedClass(u8),
+    UnsupportedEndianness(u8),
+    /// The input ends before the header (or table of headers) starting at
+    /// this offset is complete
+    TruncatedHeader {
+        offset: usize,
+    },
+    /// The file contents of a segment lie (partly) outside of the input
+    SegmentOutOfBounds {
+        index: usize,
+        range: Range<Addr>,
+    },
+    /// The file contents of a section lie (partly) outside of the input
+    SectionOutOfBounds {
+        index: usize,
+        range: Range<Addr>,
+    },
+    /// One of a pair of dynamic entries (e.g. `DT_RELA`/`DT_RELASZ`) is
+    /// present without the other
+    MissingDynamicEntry(DynamicTag),
+    /// The table is not backed by the file contents of any segment
+    SegmentNotFound(Addr),
+    /// The Rela entry at this address could not be parsed
+    InvalidRela(Addr),
+    /// The line number program of the unit at this offset of `.debug_line`
+    /// could not be parsed
+    InvalidLineProgram(usize),
+    /// The section with this name is compressed, which is not supported
+    CompressedSection(String),
+    /// Any other parsing failure, with the parsers it happened in
+    /// (innermost first) and the bytes found there
+    Parse {
+        offset: usize,
+        reason: String,
+        contexts: Vec<(usize, &'static str)>,
+        bytes: Vec<u8>,
+    },
+}
+
+impl Error {
+    pub(crate) fn from_nom(full_input: parse::Input, err: nom::Err<parse::Error>) -> Self {
+        match err {
+            nom::Err::Error(err) | nom::Err::Failure(err) => Self::from_parse(full_input, err),
+            nom::Err::Incomplete(_) => Self::TruncatedHeader {
+                offset: full_input.len(),
+            },
+        }
+    }
+
+    pub(crate) fn from_parse(full_input: parse::Input, err: parse::Error) -> Self {
+        use nom::Offset;
+        use parse::ErrorKind;
+
+        let contexts = err
+            .errors
+            .iter()
+            .filter_map(|(input, kind)| match kind {
+                ErrorKind::Context(name) => Some((full_input.offset(input), *name)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let innermost = contexts.first().map(|&(_, name)| name);
+
+        let (input, kind) = &err.errors[0];
+        let parse_error = |reason: String| Self::Parse {
+            offset: full_input.offset(input),
+            reason,
+            contexts: contexts.clone(),
+            bytes: input.iter().take(20).copied().collect(),
+        };
+
+        match kind {
+            ErrorKind::Truncated(offset) => Self::TruncatedHeader {
+                offset: (*offset).into(),
+            },
+            ErrorKind::SegmentOutOfBounds { index, range } => Self::SegmentOutOfBounds {
+                index: *index,
+                range: range.clone(),
+            },
+            ErrorKind::SectionOutOfBounds { index, range } => Self::SectionOutOfBounds {
+                index: *index,
+                range: range.clone(),
+            },
+            ErrorKind::UnknownValue(x) => match innermost {
+                Some("Class") => Self::UnsupportedClass(*x as u8),
+                Some("Endianness") => Self::UnsupportedEndianness(*x as u8),
+                _ => parse_error(format!("unknown value {:#x}", x)),
+            },
+            ErrorKind::Nom(_) if innermost == Some("Magic") => Self::BadMagic,
+            ErrorKind::Nom(nom::error::ErrorKind::Eof) => {
+                // running out of input is only a truncated file when it
+                // happens in a header, not in the contents of a segment
+                let header = contexts.iter().find(|(_, name)| {
+                    matches!(*name, "ProgramHeader" | "SectionHeader" | "SegmentContents")
+                });
+                match header {
+                    Some((_, "SegmentContents")) => parse_error("unexpected end of input".into()),
+                    Some(&(offset, _)) => Self::TruncatedHeader { offset },
+                    None => Self::TruncatedHeader { offset: 0 },
+                }
+            }
+            ErrorKind::Nom(kind) => parse_error(kind.description().to_lowercase()),
+            ErrorKind::Context(name) => parse_error(format!("invalid {}", name)),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not an ELF file (bad magic number)"),
+            Self::UnsupportedClass(x) => write!(f, "unsupported ELF class {:#x}", x),
+            Self::UnsupportedEndianness(x) => write!(f, "unsupported ELF data encoding {:#x}", x),
+            Self::TruncatedHeader { offset } => {
+                write!(
+                    f,
+                    "file is truncated: header at offset {:#x} is incomplete",
+                    offset
+                )
+            }
+            Self::SegmentOutOfBounds { index, range } => write!(
+                f,
+                "segment {} (file {:?}) extends past the end of the file",
+                index, range
+            ),
+            Self::SectionOutOfBounds { index, range } => write!(
+                f,
+                "section {} (file {:?}) extends past the end of the file",
+                index, range
+            ),
+            Self::MissingDynamicEntry(tag) => write!(f, "dynamic entry {:?} not found", tag),
+            Self::SegmentNotFound(addr) => write!(f, "no segment found for address {:?}", addr),
+            Self::InvalidRela(addr) => write!(f, "could not parse Rela entry at {:?}", addr),
+            Self::InvalidLineProgram(offset) => write!(
+                f,
+                "could not parse line number program at offset {:#x} of .debug_line",
+                offset
+            ),
+            Self::CompressedSection(name) => write!(f, "section {} is compressed", name),
+            Self::Parse {
+                offset,
+                reason,
+                contexts,
+                bytes,
+            } => {
+                write!(f, "parse error at offset {:#x}: {}", offset, reason)?;
+                for (offset, name) in contexts {
+                    write!(f, "\n    in {} at offset {:#x}", name, offset)?;
+                }
+                write!(f, "\n    {:?}", HexDump(bytes))
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use crate::{Addr, File};
+
+    const HELLO: &[u8] = include_bytes!("../../elk/samples/hello");
+
+    fn patched(offset: usize, bytes: &[u8]) -> Vec<u8> {
+        let mut input = HELLO.to_vec();
+        input[offset..][..bytes.len()].copy_from_slice(bytes);
+        input
+    }
+
+    #[test]
+    fn header_errors() {
+        assert_eq!(File::from_bytes(b"MZ\x90\0").unwrap_err(), Error::BadMagic);
+        assert_eq!(
+            File::from_bytes(&patched(4, &[3])).unwrap_err(),
+            Error::UnsupportedClass(3)
+        );
+        assert_eq!(
+            File::from_bytes(&patched(5, &[0])).unwrap_err(),
+            Error::UnsupportedEndianness(0)
+        );
+        assert_eq!(
+            File::from_bytes(&HELLO[..30]).unwrap_err(),
+            Error::TruncatedHeader { offset: 0 }
+        );
+    }
+
+    #[test]
+    fn table_errors() {
+        // e_phoff far past the end of the file
+        assert_eq!(
+            File::from_bytes(&patched(32, &0xf000_u64.to_le_bytes())).unwrap_err(),
+            Error::TruncatedHeader { offset: 0xf000 }
+        );
+
+        // p_filesz of the second program header past the end of the file
+        let (_, file) = File::parse(HELLO).unwrap();
+        let ph = &file.program_headers[1];
+        let err = File::from_bytes(&patched(64 + 56 + 32, &0xf000_u64.to_le_bytes())).unwrap_err();
+        assert_eq!(
+            err,
+            Error::SegmentOutOfBounds {
+                index: 1,
+                range: ph.offset..ph.offset + Addr(0xf000),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_error_display() {
+        // unknown p_flags bits in the first program header
+        let err = File::from_bytes(&patched(68, &[0xff, 0xff, 0, 0])).unwrap_err();
+        assert!(matches!(err, Error::Parse { offset: 68, .. }), "{:?}", err);
+        let message = err.to_string();
+        assert!(message.contains("unknown value 0xffff"), "{}", message);
+        assert!(
+            message.contains("in ProgramHeader at offset 0x40"),
+            "{}",
+            message
+        );
+        assert!(message.contains("ff ff 00 00"), "{}", message);
+    }
+}
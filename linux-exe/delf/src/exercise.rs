@@ -0,0 +1,60 @@
+use crate::{Addr, File, FileRef};
+
+/// Calls everything there is on whatever parses out of `input`, which must
+/// not panic. Shared by the property tests and the fuzz target, so that
+/// both cover the same ground.
+#[doc(hidden)]
+pub fn exercise(input: &[u8]) {
+    if let Ok(file) = FileRef::from_bytes(input) {
+        for ph in file.program_headers().flatten() {
+            let _ = file.segment_contents(&ph);
+        }
+        file.section_headers().for_each(drop);
+        let _ = file.section_by_name(".text");
+        let _ = file.to_file();
+    }
+
+    let file = match File::from_bytes(input) {
+        Ok(file) => file,
+        Err(err) => {
+            let _ = err.to_string();
+            return;
+        }
+    };
+    let addrs = [
+        Addr(0),
+        file.entry_point,
+        Addr(file.entry_point.0.wrapping_add(1)),
+        Addr(u64::MAX),
+    ];
+
+    let map = file.symbol_map();
+    for &addr in &addrs {
+        let _ = file.symbol_for_addr(addr);
+        let _ = map.lookup(addr);
+        let _ = map.at(addr);
+        let _ = file.segment_at(addr);
+        let _ = file.slice_at(addr);
+        let _ = file.addr_to_line(addr);
+        let _ = file.mapped_file_at(addr);
+    }
+    file.dynamic_symbols().for_each(drop);
+    for note in file.notes() {
+        let _ = format!("{:?}", note);
+    }
+    if let Some(build_id) = file.build_id() {
+        let _ = build_id.debug_file_path();
+    }
+    let _ = (file.threads(), file.crash_signal(), file.process_info());
+    let _ = (file.auxv(), file.mapped_files());
+    let _ = (file.needed_libraries(), file.soname(), file.bind_now());
+    let _ = (file.rela_entries(), file.plt_rela_entries());
+    for sh in &file.section_headers {
+        let _ = file.section_rela_entries(sh);
+    }
+    let _ = file.line_table();
+    let _ = file.readelf().to_string();
+    let _ = file.hardening().to_string();
+    let _ = file.diff(&file).to_string();
+    let _ = file.to_bytes();
+}
@@ -0,0 +1,537 @@
+use crate::{
+    parse, Addr, Class, Endianness, Error, File, Machine, ProgramHeader, SectionFlag,
+    SectionHeader, SectionType, SegmentContents, SegmentFlag, SegmentType, Type,
+};
+use enumflags2::BitFlags;
+use std::{borrow::Cow, fmt, ops::Range};
+
+/// Parser result for reads that do not leave any input behind
+pub(crate) type ReadResult<'a, T> = std::result::Result<T, nom::Err<parse::Error<'a>>>;
+
+/// A view of an ELF file that borrows its input (a `Vec`, or just as well a
+/// memory-mapped file) instead of copying it.
+///
+/// Only the ELF header is parsed upfront: program and section headers are
+/// parsed when asked for, and their contents are slices of the input.
+#[derive(Clone, Copy)]
+pub struct FileRef<'a> {
+    pub input: &'a [u8],
+    pub class: Class,
+    pub endianness: Endianness,
+    pub os_abi: u8,
+    pub abi_version: u8,
+    /// The rest of `e_ident`, reserved and normally zero
+    pub padding: [u8; 7],
+    pub r#type: Type,
+    pub machine: Machine,
+    pub entry_point: Addr,
+    /// Processor-specific flags
+    pub flags: u32,
+    pub hdr_size: u16,
+    pub ph_offset: Addr,
+    pub ph_entsize: u16,
+    pub ph_count: u16,
+    pub sh_offset: Addr,
+    pub sh_entsize: u16,
+    pub sh_count: u16,
+    /// Index of the section header string table (`.shstrtab`)
+    pub sh_nidx: u16,
+}
+
+impl<'a> FileRef<'a> {
+    /// Parses the ELF header and checks that the header tables it points
+    /// to are within the input
+    pub fn parse(i: parse::Input<'a>) -> parse::Result<'a, Self> {
+        use nom::{
+            bytes::complete::{tag, take},
+            combinator::{map_res, verify},
+            error::context,
+            number::complete::u8,
+            sequence::tuple,
+        };
+
+        let full_input = i;
+        let unknown = |x: u8| parse::ErrorKind::UnknownValue(x as u64);
+
+        let (i, (_, class, endianness, _, os_abi, abi_version, padding)) = tuple((
+            context("Magic", tag(File::MAGIC)),
+            context(
+                "Class",
+                map_res(u8, |x| Class::try_from(x).map_err(|_| unknown(x))),
+            ),
+            context(
+                "Endianness",
+                map_res(u8, |x| Endianness::try_from(x).map_err(|_| unknown(x))),
+            ),
+            context("Version", tag(&[0x1])),
+            context("OS ABI", u8),
+            context("ABI Version", u8),
+            context("Padding", take(7_usize)),
+        ))(i)?;
+        let ctx = parse::Context { class, endianness };
+
+        let (i, (r#type, machine)) = tuple((Type::parse(ctx), Machine::parse(ctx)))(i)?;
+        let (i, _) = context("Version (bis)", verify(ctx.u32(), |&x| x == 1))(i)?;
+        let (i, entry_point) = Addr::parse(ctx)(i)?;
+
+        // ph = program header, sh = section header
+        let (i, (ph_offset, sh_offset)) = tuple((Addr::parse(ctx), Addr::parse(ctx)))(i)?;
+        let (i, (flags, hdr_size)) = tuple((ctx.u32(), ctx.u16()))(i)?;
+        let (i, (ph_entsize, ph_count)) = tuple((ctx.u16(), ctx.u16()))(i)?;
+        let (i, (sh_entsize, sh_count, sh_nidx)) = tuple((ctx.u16(), ctx.u16(), ctx.u16()))(i)?;
+
+        let res = Self {
+            input: full_input,
+            class,
+            endianness,
+            os_abi,
+            abi_version,
+            padding: padding.try_into().unwrap(),
+            r#type,
+            machine,
+            entry_point,
+            flags,
+            hdr_size,
+            ph_offset,
+            ph_entsize,
+            ph_count,
+            sh_offset,
+            sh_entsize,
+            sh_count,
+            sh_nidx,
+        };
+        res.table(res.ph_offset, res.ph_entsize, res.ph_count)?;
+        res.table(res.sh_offset, res.sh_entsize, res.sh_count)?;
+        Ok((i, res))
+    }
+
+    pub fn from_bytes(i: &'a [u8]) -> Result<Self, Error> {
+        Self::parse(i)
+            .map(|(_, file)| file)
+            .map_err(|err| Error::from_nom(i, err))
+    }
+
+    /// Copies the whole file into an owned `File`
+    pub fn to_file(&self) -> Result<File, Error> {
+        File::from_ref(self).map_err(|err| self.error(err))
+    }
+
+    pub(crate) fn context(&self) -> parse::Context {
+        parse::Context {
+            class: self.class,
+            endianness: self.endianness,
+        }
+    }
+
+    fn error(&self, err: nom::Err<parse::Error<'a>>) -> Error {
+        Error::from_nom(self.input, err)
+    }
+
+    fn table(&self, offset: Addr, entsize: u16, count: u16) -> ReadResult<'a, parse::Input<'a>> {
+        // relocatable object files have no program headers, and leave
+        // their offset and entry size empty
+        if count == 0 {
+            return Ok(&[]);
+        }
+        parse::slice(self.input, offset, Addr(entsize as u64 * count as u64))
+            .ok_or_else(|| parse::Error::failure(self.input, parse::ErrorKind::Truncated(offset)))
+    }
+
+    fn table_entry(
+        &self,
+        offset: Addr,
+        entsize: u16,
+        count: u16,
+        index: usize,
+    ) -> ReadResult<'a, parse::Input<'a>> {
+        let table = self.table(offset, entsize, count)?;
+        let entsize = entsize as usize;
+        // out-of-range entries come out empty, and fail to parse
+        Ok(table
+            .get(index * entsize..(index + 1) * entsize)
+            .unwrap_or_default())
+    }
+
+    pub(crate) fn read_program_header(&self, index: usize) -> ReadResult<'a, ProgramHeaderRef<'a>> {
+        use nom::error::context;
+
+        let ctx = self.context();
+        let i = self.table_entry(self.ph_offset, self.ph_entsize, self.ph_count, index)?;
+        let (_, mut ph) = context("ProgramHeader", |i| ProgramHeaderRef::parse(ctx, i))(i)?;
+        ph.data = parse::slice(self.input, ph.offset, ph.filesz).ok_or_else(|| {
+            let range = ph.file_range();
+            parse::Error::failure(i, parse::ErrorKind::SegmentOutOfBounds { index, range })
+        })?;
+        Ok(ph)
+    }
+
+    pub(crate) fn read_segment_contents(
+        &self,
+        ph: &ProgramHeaderRef<'a>,
+    ) -> ReadResult<'a, SegmentContents> {
+        use nom::error::context;
+
+        let ctx = self.context();
+        let (_, contents) = context("SegmentContents", |i| {
+            SegmentContents::parse(ctx, self.machine, ph.r#type, ph.align, i)
+        })(ph.data)?;
+        Ok(contents)
+    }
+
+    /// Reads a section header without resolving its name
+    fn read_unnamed_section_header(&self, index: usize) -> ReadResult<'a, SectionHeaderRef<'a>> {
+        use nom::error::context;
+
+        let ctx = self.context();
+        let i = self.table_entry(self.sh_offset, self.sh_entsize, self.sh_count, index)?;
+        let (_, mut sh) = context("SectionHeader", |i| SectionHeaderRef::parse(ctx, i))(i)?;
+        sh.data = parse::slice(self.input, sh.offset, sh.file_size()).ok_or_else(|| {
+            let range = sh.file_range();
+            parse::Error::failure(i, parse::ErrorKind::SectionOutOfBounds { index, range })
+        })?;
+        Ok(sh)
+    }
+
+    pub(crate) fn read_section_header(&self, index: usize) -> ReadResult<'a, SectionHeaderRef<'a>> {
+        let mut sh = self.read_unnamed_section_header(index)?;
+        if let Ok(shstrtab) = self.read_unnamed_section_header(self.sh_nidx as usize) {
+            if let Some(name) = shstrtab.get_string(sh.name_offset as usize) {
+                sh.name = name;
+            }
+        }
+        Ok(sh)
+    }
+
+    pub fn program_headers(&self) -> impl Iterator<Item = Result<ProgramHeaderRef<'a>, Error>> {
+        let file = *self;
+        (0..file.ph_count as usize).map(move |index| {
+            file.read_program_header(index)
+                .map_err(|err| file.error(err))
+        })
+    }
+
+    pub fn section_headers(&self) -> impl Iterator<Item = Result<SectionHeaderRef<'a>, Error>> {
+        let file = *self;
+        (0..file.sh_count as usize).map(move |index| {
+            file.read_section_header(index)
+                .map_err(|err| file.error(err))
+        })
+    }
+
+    /// Decodes the contents of segments such as `PT_DYNAMIC`
+    pub fn segment_contents(&self, ph: &ProgramHeaderRef<'a>) -> Result<SegmentContents, Error> {
+        self.read_segment_contents(ph)
+            .map_err(|err| self.error(err))
+    }
+
+    /// Section headers that fail to parse are skipped.
+    pub fn section_by_name(&self, name: &str) -> Option<SectionHeaderRef<'a>> {
+        self.section_headers()
+            .filter_map(Result::ok)
+            .find(|sh| sh.name == name)
+    }
+}
+
+impl fmt::Debug for FileRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {:?} {:?} {:?} | entry {:?} | {} segments, {} sections",
+            self.class,
+            self.endianness,
+            self.r#type,
+            self.machine,
+            self.entry_point,
+            self.ph_count,
+            self.sh_count
+        )
+    }
+}
+
+/// Like `ProgramHeader`, with the segment contents borrowed from the input
+#[derive(Clone)]
+pub struct ProgramHeaderRef<'a> {
+    pub r#type: SegmentType,
+    pub flags: BitFlags<SegmentFlag>,
+    pub offset: Addr,
+    pub vaddr: Addr,
+    pub paddr: Addr,
+    pub filesz: Addr,
+    pub memsz: Addr,
+    pub align: Addr,
+    pub data: &'a [u8],
+}
+
+impl<'a> ProgramHeaderRef<'a> {
+    pub fn file_range(&self) -> Range<Addr> {
+        self.offset.range(self.filesz)
+    }
+
+    pub fn mem_range(&self) -> Range<Addr> {
+        self.vaddr.range(self.memsz)
+    }
+
+    /// Parses the header only: `data` is filled in by `FileRef`, which
+    /// knows where the file contents are.
+    pub fn parse(ctx: parse::Context, i: parse::Input<'a>) -> parse::Result<'a, Self> {
+        use nom::sequence::tuple;
+        let ap = &Addr::parse(ctx);
+
+        // ELF32 moves the flags after the sizes, to keep the fields aligned
+        let (i, (r#type, flags, offset, vaddr, paddr, filesz, memsz, align)) = match ctx.class {
+            Class::Elf64 => {
+                let (i, (r#type, flags)) =
+                    tuple((SegmentType::parse(ctx), SegmentFlag::parse(ctx)))(i)?;
+                let (i, (offset, vaddr, paddr, filesz, memsz, align)) =
+                    tuple((ap, ap, ap, ap, ap, ap))(i)?;
+                (
+                    i,
+                    (r#type, flags, offset, vaddr, paddr, filesz, memsz, align),
+                )
+            }
+            Class::Elf32 => {
+                let (i, (r#type, offset, vaddr, paddr, filesz, memsz)) =
+                    tuple((SegmentType::parse(ctx), ap, ap, ap, ap, ap))(i)?;
+                let (i, (flags, align)) = tuple((SegmentFlag::parse(ctx), ap))(i)?;
+                (
+                    i,
+                    (r#type, flags, offset, vaddr, paddr, filesz, memsz, align),
+                )
+            }
+        };
+
+        let res = Self {
+            r#type,
+            flags,
+            offset,
+            vaddr,
+            paddr,
+            filesz,
+            memsz,
+            align,
+            data: &[],
+        };
+        Ok((i, res))
+    }
+
+    pub(crate) fn into_owned(self, contents: SegmentContents) -> ProgramHeader {
+        ProgramHeader {
+            r#type: self.r#type,
+            flags: self.flags,
+            offset: self.offset,
+            vaddr: self.vaddr,
+            paddr: self.paddr,
+            filesz: self.filesz,
+            memsz: self.memsz,
+            align: self.align,
+            data: self.data.to_vec(),
+            contents,
+        }
+    }
+}
+
+impl fmt::Debug for ProgramHeaderRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "file {:?} | mem {:?} | align {:?} | {:?} {:?}",
+            self.file_range(),
+            self.mem_range(),
+            self.align,
+            self.flags,
+            self.r#type
+        )
+    }
+}
+
+/// Like `SectionHeader`, with the name and contents borrowed from the input
+#[derive(Clone)]
+pub struct SectionHeaderRef<'a> {
+    /// Resolved through the section header string table (`.shstrtab`),
+    /// only allocated if it is not valid UTF-8
+    pub name: Cow<'a, str>,
+    /// Offset of the name in the section header string table
+    pub name_offset: u32,
+    pub r#type: SectionType,
+    pub flags: BitFlags<SectionFlag>,
+    /// Flag bits without a `SectionFlag`, such as OS- and
+    /// processor-specific ones
+    pub other_flags: u64,
+    pub addr: Addr,
+    pub offset: Addr,
+    pub size: Addr,
+    pub link: u32,
+    pub info: u32,
+    pub addralign: Addr,
+    pub entsize: Addr,
+    pub data: &'a [u8],
+}
+
+impl<'a> SectionHeaderRef<'a> {
+    pub fn file_range(&self) -> Range<Addr> {
+        self.offset.range(self.file_size())
+    }
+
+    pub fn mem_range(&self) -> Range<Addr> {
+        self.addr.range(self.size)
+    }
+
+    fn file_size(&self) -> Addr {
+        self.r#type.file_size(self.size)
+    }
+
+    /// Parses the header only: `name` and `data` are filled in by
+    /// `FileRef`.
+    pub fn parse(ctx: parse::Context, i: parse::Input<'a>) -> parse::Result<'a, Self> {
+        use nom::sequence::tuple;
+        let (i, (name_offset, r#type, (flags, other_flags))) =
+            tuple((ctx.u32(), SectionType::parse(ctx), SectionFlag::parse(ctx)))(i)?;
+
+        let ap = &Addr::parse(ctx);
+        let (i, (addr, offset, size)) = tuple((ap, ap, ap))(i)?;
+        let (i, (link, info)) = tuple((ctx.u32(), ctx.u32()))(i)?;
+        let (i, (addralign, entsize)) = tuple((ap, ap))(i)?;
+
+        let res = Self {
+            name: Cow::Borrowed(""),
+            name_offset,
+            r#type,
+            flags,
+            other_flags,
+            addr,
+            offset,
+            size,
+            link,
+            info,
+            addralign,
+            entsize,
+            data: &[],
+        };
+        Ok((i, res))
+    }
+
+    /// Returns the null-terminated string starting at `offset`, for
+    /// string table sections such as `.shstrtab`, `.strtab` and `.dynstr`.
+    pub fn get_string(&self, offset: usize) -> Option<Cow<'a, str>> {
+        let bytes = self.data.get(offset..)?;
+        let len = bytes.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&bytes[..len]))
+    }
+
+    pub(crate) fn into_owned(self) -> SectionHeader {
+        SectionHeader {
+            name: self.name.into_owned(),
+            name_offset: self.name_offset,
+            r#type: self.r#type,
+            flags: self.flags,
+            other_flags: self.other_flags,
+            addr: self.addr,
+            offset: self.offset,
+            size: self.size,
+            link: self.link,
+            info: self.info,
+            addralign: self.addralign,
+            entsize: self.entsize,
+            data: self.data.to_vec(),
+        }
+    }
+}
+
+impl fmt::Debug for SectionHeaderRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<20} | file {:?} | mem {:?} | {:?} {:?}",
+            self.name,
+            self.file_range(),
+            self.mem_range(),
+            self.flags,
+            self.r#type
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Addr, Error, File, FileRef, SegmentContents, SegmentType};
+
+    fn contains(input: &[u8], data: &[u8]) -> bool {
+        input.as_ptr_range().start <= data.as_ptr()
+            && data.as_ptr_range().end <= input.as_ptr_range().end
+    }
+
+    #[test]
+    fn borrows_input() {
+        let input = include_bytes!("../../elk/samples/hello");
+        let file = FileRef::from_bytes(&input[..]).unwrap();
+        assert_eq!(file.entry_point, Addr(0x401000));
+
+        for ph in file.program_headers() {
+            let ph = ph.unwrap();
+            assert_eq!(ph.data.len(), usize::from(ph.filesz));
+            assert!(contains(input, ph.data));
+        }
+
+        let data = file.section_by_name(".data").unwrap();
+        assert_eq!(data.data, b"hi there\n");
+        assert!(contains(input, data.data));
+        assert!(matches!(data.name, std::borrow::Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn same_as_owned() {
+        let input = include_bytes!("../../elk/samples/entrypoint");
+        let file = FileRef::from_bytes(&input[..]).unwrap();
+        let owned = File::from_bytes(&input[..]).unwrap();
+        assert_eq!(
+            format!("{:?}", file.to_file().unwrap()),
+            format!("{:?}", owned)
+        );
+
+        let names = file
+            .section_headers()
+            .map(|sh| sh.unwrap().name.into_owned())
+            .collect::<Vec<_>>();
+        let owned_names = owned
+            .section_headers
+            .iter()
+            .map(|sh| sh.name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, owned_names);
+
+        let dynamic = file
+            .program_headers()
+            .map(Result::unwrap)
+            .find(|ph| ph.r#type == SegmentType::Dynamic)
+            .unwrap();
+        match file.segment_contents(&dynamic).unwrap() {
+            SegmentContents::Dynamic(entries) => {
+                assert_eq!(entries.len(), owned.dynamic_table().unwrap().len())
+            }
+            _ => panic!("expected dynamic entries"),
+        }
+    }
+
+    #[test]
+    fn parses_lazily() {
+        // point the size of `.data` (section 2) past the end of the file:
+        // only that section fails to read
+        let mut input = include_bytes!("../../elk/samples/hello").to_vec();
+        let (_, file) = File::parse(&input[..]).unwrap();
+        let size_offset = usize::from(file.sh_offset) + 2 * 64 + 32;
+        input[size_offset..][..8].copy_from_slice(&0xf000_u64.to_le_bytes());
+
+        let file = FileRef::from_bytes(&input[..]).unwrap();
+        let results = file.section_headers().collect::<Vec<_>>();
+        assert!(matches!(
+            results[2],
+            Err(Error::SectionOutOfBounds { index: 2, .. })
+        ));
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 5);
+        assert!(file.section_by_name(".text").is_some());
+        assert!(matches!(
+            file.to_file(),
+            Err(Error::SectionOutOfBounds { index: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+//! Which exploit mitigations a file was built with, like `checksec` reports
+
+use crate::{File, SectionFlag, SegmentFlag, SegmentType, Type};
+use serde::Serialize;
+use std::fmt;
+
+/// Whether the file is loaded at a random address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Pie {
+    /// An executable that must be loaded at fixed addresses
+    No,
+    Yes,
+    /// A library, always position-independent
+    SharedObject,
+    /// Relocatable objects and core dumps, which are not loaded as such
+    NotLoadable,
+}
+
+/// How much of the data the dynamic loader writes to is read-only by the
+/// time the program runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Relro {
+    None,
+    /// `PT_GNU_RELRO`, but the GOT entries of functions are bound lazily
+    /// and stay writable
+    Partial,
+    /// `PT_GNU_RELRO` with everything bound at load time
+    Full,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Hardening {
+    pub pie: Pie,
+    /// The stack is not executable: `PT_GNU_STACK` (or the
+    /// `.note.GNU-stack` section of a relocatable object) without the
+    /// execute flag. Without either, the stack is executable.
+    pub nx: bool,
+    pub relro: Relro,
+    /// Uses stack protector symbols, such as `__stack_chk_fail`
+    pub canary: bool,
+    /// Fortified functions (`-D_FORTIFY_SOURCE`) used, such as
+    /// `__memcpy_chk`, sorted. Symbols only tell for dynamically-linked
+    /// files: static executables have whatever their libc has.
+    pub fortified: Vec<String>,
+    pub rpath: Option<String>,
+    pub runpath: Option<String>,
+    /// Indices of the `Load` segments that are both writable and executable
+    pub wx_segments: Vec<usize>,
+}
+
+const CANARY_SYMBOLS: &[&str] = &[
+    "__stack_chk_fail",
+    "__stack_chk_fail_local",
+    "__stack_chk_guard",
+    "__intel_security_cookie",
+];
+
+impl File {
+    /// Reports the exploit mitigations of the file, from its program
+    /// headers, dynamic table and symbols
+    pub fn hardening(&self) -> Hardening {
+        let pie = match self.r#type {
+            Type::Exec => Pie::No,
+            Type::Dyn if self.is_pie() => Pie::Yes,
+            Type::Dyn => Pie::SharedObject,
+            Type::None | Type::Rel | Type::Core => Pie::NotLoadable,
+        };
+
+        let nx = if self.program_headers.is_empty() {
+            self.section_by_name(".note.GNU-stack")
+                .is_some_and(|sh| !sh.flags.contains(SectionFlag::ExecInstr))
+        } else {
+            self.segment_of_type(SegmentType::GnuStack)
+                .is_some_and(|ph| !ph.flags.contains(SegmentFlag::Execute))
+        };
+
+        let relro = match self.segment_of_type(SegmentType::GnuRelRo) {
+            None => Relro::None,
+            Some(_) if self.bind_now() => Relro::Full,
+            Some(_) => Relro::Partial,
+        };
+
+        // what a dynamically-linked file calls, rather than provides (like
+        // libc does), but static executables bring everything along
+        let is_static = self.dynamic_table().is_none();
+        let names = self
+            .symbols()
+            .chain(self.dynamic_symbols())
+            .filter(|sym| is_static || sym.shndx.is_undef())
+            .map(|sym| sym.name)
+            .collect::<Vec<_>>();
+        let canary = names
+            .iter()
+            .any(|name| CANARY_SYMBOLS.contains(&name.as_str()));
+        let mut fortified = names
+            .into_iter()
+            .filter(|name| {
+                name.starts_with("__")
+                    && name.ends_with("_chk")
+                    && !CANARY_SYMBOLS.contains(&name.as_str())
+            })
+            .collect::<Vec<_>>();
+        fortified.sort();
+        fortified.dedup();
+
+        let wx_segments = self
+            .program_headers
+            .iter()
+            .enumerate()
+            .filter(|(_, ph)| {
+                ph.r#type == SegmentType::Load
+                    && ph.flags.contains(SegmentFlag::Write | SegmentFlag::Execute)
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        Hardening {
+            pie,
+            nx,
+            relro,
+            canary,
+            fortified,
+            rpath: self.rpath(),
+            runpath: self.runpath(),
+            wx_segments,
+        }
+    }
+}
+
+impl fmt::Display for Hardening {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let relro = match self.relro {
+            Relro::None => "No RELRO",
+            Relro::Partial => "Partial RELRO",
+            Relro::Full => "Full RELRO",
+        };
+        writeln!(f, "RELRO:    {}", relro)?;
+        let canary = match self.canary {
+            true => "Canary found",
+            false => "No canary found",
+        };
+        writeln!(f, "Stack:    {}", canary)?;
+        let nx = match self.nx {
+            true => "NX enabled",
+            false => "NX disabled",
+        };
+        writeln!(f, "NX:       {}", nx)?;
+        let pie = match self.pie {
+            Pie::No => "No PIE",
+            Pie::Yes => "PIE enabled",
+            Pie::SharedObject => "DSO",
+            Pie::NotLoadable => "Not loadable",
+        };
+        writeln!(f, "PIE:      {}", pie)?;
+        match self.fortified.as_slice() {
+            [] => writeln!(f, "FORTIFY:  No")?,
+            names => writeln!(f, "FORTIFY:  Yes ({})", names.join(", "))?,
+        }
+        match &self.rpath {
+            Some(rpath) => writeln!(f, "RPATH:    {:?}", rpath)?,
+            None => writeln!(f, "RPATH:    No RPATH")?,
+        }
+        match &self.runpath {
+            Some(runpath) => writeln!(f, "RUNPATH:  {:?}", runpath)?,
+            None => writeln!(f, "RUNPATH:  No RUNPATH")?,
+        }
+        match self.wx_segments.as_slice() {
+            [] => writeln!(f, "W+X:      No W+X segments"),
+            indices => {
+                let indices = indices.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+                writeln!(f, "W+X:      Segments {}", indices.join(", "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pie, Relro};
+    use crate::{File, SegmentFlag};
+
+    fn parse(input: &[u8]) -> File {
+        File::from_bytes(input).unwrap()
+    }
+
+    #[test]
+    fn executables() {
+        let h = parse(include_bytes!("../../elk/samples/entrypoint")).hardening();
+        assert_eq!(h.pie, Pie::No);
+        assert!(h.nx);
+        assert_eq!(h.relro, Relro::Full);
+        assert!(!h.canary);
+        assert_eq!(h.fortified, ["__printf_chk"]);
+        assert!(h.runpath.is_some());
+
+        // hand-written assembly, without a `.note.GNU-stack` section
+        let h = parse(include_bytes!("../../elk/samples/greet")).hardening();
+        assert_eq!(h.pie, Pie::Yes);
+        assert!(!h.nx);
+        assert_eq!(h.relro, Relro::Partial);
+        assert_eq!(h.runpath.as_deref(), Some("$ORIGIN"));
+        assert_eq!(h.rpath, None);
+
+        let h = parse(include_bytes!("../../elk/samples/hello")).hardening();
+        assert_eq!(h.pie, Pie::No);
+        assert_eq!(h.relro, Relro::None);
+        assert!(h.wx_segments.is_empty());
+    }
+
+    #[test]
+    fn other_types() {
+        let h = parse(include_bytes!("../../elk/samples/libgreet.so")).hardening();
+        assert_eq!(h.pie, Pie::SharedObject);
+        let h = parse(include_bytes!("../../elk/samples/hello.o")).hardening();
+        assert_eq!(h.pie, Pie::NotLoadable);
+    }
+
+    #[test]
+    fn pie_or_library() {
+        use crate::{DynamicTag, SegmentContents, SegmentType};
+        fn dynamic_table(file: &mut File) -> &mut Vec<crate::DynamicEntry> {
+            let ph = file
+                .program_headers
+                .iter_mut()
+                .find(|ph| ph.r#type == SegmentType::Dynamic)
+                .unwrap();
+            match &mut ph.contents {
+                SegmentContents::Dynamic(entries) => entries,
+                _ => panic!("no dynamic table"),
+            }
+        }
+
+        // a library with an interpreter, like libc.so.6
+        let mut file = parse(include_bytes!("../../elk/samples/libgreet.so"));
+        file.program_headers[2].r#type = SegmentType::Interp;
+        assert_eq!(file.hardening().pie, Pie::SharedObject);
+
+        // an executable from a linker that doesn't set DF_1_PIE
+        let mut file = parse(include_bytes!("../../elk/samples/greet"));
+        dynamic_table(&mut file).retain(|e| e.tag != DynamicTag::Flags1);
+        assert_eq!(file.hardening().pie, Pie::Yes);
+        // which readelf agrees with
+        let readelf = file.readelf().to_string();
+        assert!(readelf.contains("DYN (Position-Independent Executable file)"));
+        // which can't be told from such a library
+        let soname = dynamic_table(&mut file)
+            .iter_mut()
+            .find(|e| e.tag == DynamicTag::RunPath)
+            .unwrap();
+        soname.tag = DynamicTag::SoName;
+        assert_eq!(file.hardening().pie, Pie::SharedObject);
+    }
+
+    #[test]
+    fn writable_and_executable() {
+        let mut file = parse(include_bytes!("../../elk/samples/hello"));
+        file.program_headers[1].flags |= SegmentFlag::Write;
+        let h = file.hardening();
+        assert_eq!(h.wx_segments, [1]);
+        assert!(h.to_string().contains("W+X:      Segments 1\n"));
+    }
+}
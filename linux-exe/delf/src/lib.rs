@@ -1,11 +1,55 @@
+mod core_dump;
+mod debug_line;
+mod diff;
+mod error;
+mod exercise;
+mod file_ref;
+mod hardening;
+mod note;
 mod parse;
+#[cfg(test)]
+mod proptests;
+mod readelf;
+mod ser;
+mod write;
+
+pub use core_dump::{
+    AuxType, AuxvEntry, CoreNoteType, MappedFile, PrPsInfo, PrStatus, X86_64Registers,
+};
+pub use debug_line::LineTable;
+pub use diff::{
+    Change, Diff, SectionChange, SectionSummary, SegmentChange, SegmentSummary, Size, SymbolChange,
+    SymbolSummary,
+};
+pub use error::Error;
+#[doc(hidden)]
+pub use exercise::exercise;
+pub use file_ref::{FileRef, ProgramHeaderRef, SectionHeaderRef};
+pub use hardening::{Hardening, Pie, Relro};
+pub use note::{AbiTagOs, BuildId, GnuNoteType, GnuProperty, Note, X86Feature};
+pub use readelf::ReadElf;
 
 use derive_more::{Add, Sub};
 use derive_try_from_primitive::TryFromPrimitive;
 use enumflags2::*;
+use serde::Serialize;
 use std::{fmt, ops::Range};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, Serialize)]
+#[repr(u8)]
+pub enum Class {
+    Elf32 = 0x1,
+    Elf64 = 0x2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, Serialize)]
+#[repr(u8)]
+pub enum Endianness {
+    Little = 0x1,
+    Big = 0x2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, Serialize)]
 #[repr(u16)]
 pub enum Type {
     None = 0x0,
@@ -14,26 +58,56 @@ pub enum Type {
     Dyn = 0x3,
     Core = 0x4,
 }
-impl_parse_for_enum!(Type, le_u16);
+impl_parse_for_enum!(Type, u16);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
-#[repr(u16)]
-pub enum Machine {
-    X86 = 0x03,
-    X86_64 = 0x3e,
+open_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+    pub enum Machine: u16 {
+        None = 0x00,
+        Sparc = 0x02,
+        X86 = 0x03,
+        M68k = 0x04,
+        Mips = 0x08,
+        MipsRs3Le = 0x0a,
+        PaRisc = 0x0f,
+        PowerPC = 0x14,
+        PowerPC64 = 0x15,
+        S390 = 0x16,
+        Arm = 0x28,
+        SuperH = 0x2a,
+        SparcV9 = 0x2b,
+        Ia64 = 0x32,
+        X86_64 = 0x3e,
+        Avr = 0x53,
+        Xtensa = 0x5e,
+        Msp430 = 0x69,
+        AArch64 = 0xb7,
+        RiscV = 0xf3,
+        Bpf = 0xf7,
+        LoongArch = 0x102,
+    }
 }
-impl_parse_for_enum!(Machine, le_u16);
+impl_parse_for_enum!(Machine, u16);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
-#[repr(u32)]
-pub enum SegmentType {
-    Null = 0x0,
-    Load = 0x1,
-    Dynamic = 0x2,
-    Interp = 0x3,
-    Note = 0x4,
+open_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+    pub enum SegmentType: u32 {
+        Null = 0x0,
+        Load = 0x1,
+        Dynamic = 0x2,
+        Interp = 0x3,
+        Note = 0x4,
+        ShLib = 0x5,
+        Phdr = 0x6,
+        Tls = 0x7,
+        GnuEhFrame = 0x6474e550,
+        GnuStack = 0x6474e551,
+        GnuRelRo = 0x6474e552,
+        GnuProperty = 0x6474e553,
+        GnuSFrame = 0x6474e554,
+    }
 }
-impl_parse_for_enum!(SegmentType, le_u32);
+impl_parse_for_enum!(SegmentType, u32);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[bitflags]
@@ -43,9 +117,92 @@ pub enum SegmentFlag {
     Write = 0x2,
     Read = 0x4,
 }
-impl_parse_for_enumflags!(SegmentFlag, le_u32);
+impl_parse_for_enumflags!(SegmentFlag, u32);
+
+open_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+    pub enum SectionType: u32 {
+        Null = 0x0,
+        ProgBits = 0x1,
+        SymTab = 0x2,
+        StrTab = 0x3,
+        Rela = 0x4,
+        Hash = 0x5,
+        Dynamic = 0x6,
+        Note = 0x7,
+        NoBits = 0x8,
+        Rel = 0x9,
+        ShLib = 0xa,
+        DynSym = 0xb,
+        InitArray = 0xe,
+        FiniArray = 0xf,
+        PreInitArray = 0x10,
+        Group = 0x11,
+        SymTabShndx = 0x12,
+        Relr = 0x13,
+        GnuAttributes = 0x6ffffff5,
+        GnuHash = 0x6ffffff6,
+        GnuLibList = 0x6ffffff7,
+        GnuVerDef = 0x6ffffffd,
+        GnuVerNeed = 0x6ffffffe,
+        GnuVerSym = 0x6fffffff,
+        // processor-specific types mean different things for different
+        // machines, and are left as `Other`
+    }
+}
+impl_parse_for_enum!(SectionType, u32);
+
+impl SectionType {
+    fn file_size(self, size: Addr) -> Addr {
+        match self {
+            SectionType::NoBits => Addr(0),
+            _ => size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[bitflags]
+#[repr(u64)]
+pub enum SectionFlag {
+    Write = 0x1,
+    Alloc = 0x2,
+    ExecInstr = 0x4,
+    Merge = 0x10,
+    Strings = 0x20,
+    InfoLink = 0x40,
+    LinkOrder = 0x80,
+    OsNonConforming = 0x100,
+    Group = 0x200,
+    Tls = 0x400,
+    Compressed = 0x800,
+    GnuRetain = 0x200000,
+    Exclude = 0x80000000,
+}
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Add, Sub)]
+impl SectionFlag {
+    /// Flags such as `SHF_X86_64_LARGE`, whose meaning depends on the OS or
+    /// processor
+    pub const MASK_OS: u64 = 0x0ff0_0000;
+    pub const MASK_PROC: u64 = 0xf000_0000;
+
+    /// Splits flags into the ones known here and the rest, which only
+    /// some OS or processor knows about
+    pub fn parse<'a>(
+        ctx: parse::Context,
+    ) -> impl Fn(parse::Input<'a>) -> parse::Result<'a, (BitFlags<Self>, u64)> {
+        use nom::{combinator::map, error::context};
+        move |i| {
+            let parser = map(ctx.word(), |x| {
+                let flags = BitFlags::<Self>::from_bits_truncate(x);
+                (flags, x & !flags.bits())
+            });
+            context("SectionFlag", parser)(i)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Add, Sub, Serialize)]
 pub struct Addr(pub u64);
 impl fmt::Debug for Addr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -58,15 +215,15 @@ impl fmt::Display for Addr {
         fmt::Debug::fmt(self, f)
     }
 }
-impl Into<u64> for Addr {
-    fn into(self) -> u64 {
-        self.0
+impl From<Addr> for u64 {
+    fn from(addr: Addr) -> Self {
+        addr.0
     }
 }
 
-impl Into<usize> for Addr {
-    fn into(self) -> usize {
-        self.0 as usize
+impl From<Addr> for usize {
+    fn from(addr: Addr) -> Self {
+        addr.0 as usize
     }
 }
 
@@ -77,14 +234,152 @@ impl From<u64> for Addr {
 }
 
 impl Addr {
-    pub fn parse(i: parse::Input) -> parse::Result<Self> {
-        use nom::{combinator::map, number::complete::le_u64};
-        map(le_u64, From::from)(i)
+    /// Addresses are 32 or 64 bits wide depending on the file's class, and
+    /// always widened to 64 bits.
+    pub fn parse<'a>(ctx: parse::Context) -> impl Fn(parse::Input<'a>) -> parse::Result<'a, Self> {
+        use nom::combinator::map;
+        move |i| map(ctx.word(), From::from)(i)
+    }
+
+    /// The `len` bytes starting at `self`, cut short at the end of the
+    /// address space: offsets and sizes come from the file and can be
+    /// anything
+    pub fn range(self, len: Addr) -> Range<Addr> {
+        self..Addr(self.0.saturating_add(len.0))
+    }
+}
+
+open_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+    pub enum DynamicTag: u64 {
+        Null = 0,
+        Needed = 1,
+        PltRelSz = 2,
+        PltGot = 3,
+        Hash = 4,
+        StrTab = 5,
+        SymTab = 6,
+        Rela = 7,
+        RelaSz = 8,
+        RelaEnt = 9,
+        StrSz = 10,
+        SymEnt = 11,
+        Init = 12,
+        Fini = 13,
+        SoName = 14,
+        RPath = 15,
+        Symbolic = 16,
+        Rel = 17,
+        RelSz = 18,
+        RelEnt = 19,
+        PltRel = 20,
+        Debug = 21,
+        TextRel = 22,
+        JmpRel = 23,
+        BindNow = 24,
+        InitArray = 25,
+        FiniArray = 26,
+        InitArraySz = 27,
+        FiniArraySz = 28,
+        RunPath = 29,
+        Flags = 30,
+        PreInitArray = 32,
+        PreInitArraySz = 33,
+        SymTabShndx = 34,
+        RelrSz = 35,
+        Relr = 36,
+        RelrEnt = 37,
+        GnuPrelinked = 0x6ffffdf5,
+        GnuConflictSz = 0x6ffffdf6,
+        GnuLibListSz = 0x6ffffdf7,
+        Checksum = 0x6ffffdf8,
+        PltPadSz = 0x6ffffdf9,
+        MoveEnt = 0x6ffffdfa,
+        MoveSz = 0x6ffffdfb,
+        PosFlag1 = 0x6ffffdfd,
+        SymInSz = 0x6ffffdfe,
+        SymInEnt = 0x6ffffdff,
+        GnuHash = 0x6ffffef5,
+        TlsDescPlt = 0x6ffffef6,
+        TlsDescGot = 0x6ffffef7,
+        GnuConflict = 0x6ffffef8,
+        GnuLibList = 0x6ffffef9,
+        Config = 0x6ffffefa,
+        DepAudit = 0x6ffffefb,
+        Audit = 0x6ffffefc,
+        PltPad = 0x6ffffefd,
+        MoveTab = 0x6ffffefe,
+        SymInfo = 0x6ffffeff,
+        VerSym = 0x6ffffff0,
+        RelaCount = 0x6ffffff9,
+        RelCount = 0x6ffffffa,
+        Flags1 = 0x6ffffffb,
+        VerDef = 0x6ffffffc,
+        VerDefNum = 0x6ffffffd,
+        VerNeed = 0x6ffffffe,
+        VerNeedNum = 0x6fffffff,
+        Auxiliary = 0x7ffffffd,
+        Filter = 0x7fffffff,
+    }
+}
+impl_parse_for_enum!(DynamicTag, word);
+
+/// Bit of `DynamicTag::Flags1` set by linkers on position-independent
+/// executables
+pub const DF_1_PIE: u64 = 0x0800_0000;
+
+#[derive(Debug, Serialize)]
+pub struct DynamicEntry {
+    pub tag: DynamicTag,
+    pub addr: Addr,
+}
+
+impl DynamicEntry {
+    pub fn parse(ctx: parse::Context, i: parse::Input) -> parse::Result<Self> {
+        use nom::sequence::tuple;
+        let (i, (tag, addr)) = tuple((DynamicTag::parse(ctx), Addr::parse(ctx)))(i)?;
+        Ok((i, Self { tag, addr }))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub enum SegmentContents {
+    Dynamic(Vec<DynamicEntry>),
+    Note(Vec<Note>),
+    Unknown,
+}
+
+impl SegmentContents {
+    pub fn parse(
+        ctx: parse::Context,
+        machine: Machine,
+        r#type: SegmentType,
+        align: Addr,
+        i: parse::Input,
+    ) -> parse::Result<Self> {
+        use nom::{combinator::verify, multi::many_till};
+        match r#type {
+            SegmentType::Dynamic => {
+                // the dynamic table is terminated by a `Null` entry
+                let entry = |i| DynamicEntry::parse(ctx, i);
+                let (i, (mut entries, last)) =
+                    many_till(entry, verify(entry, |e| e.tag == DynamicTag::Null))(i)?;
+                entries.push(last);
+                Ok((i, SegmentContents::Dynamic(entries)))
+            }
+            SegmentType::Note => {
+                let (i, notes) = Note::parse_all(ctx, machine, align, i)?;
+                Ok((i, SegmentContents::Note(notes)))
+            }
+            _ => Ok((i, SegmentContents::Unknown)),
+        }
     }
 }
 
+#[derive(Serialize)]
 pub struct ProgramHeader {
     pub r#type: SegmentType,
+    #[serde(serialize_with = "ser::flags")]
     pub flags: BitFlags<SegmentFlag>,
     pub offset: Addr,
     pub vaddr: Addr,
@@ -92,38 +387,19 @@ pub struct ProgramHeader {
     pub filesz: Addr,
     pub memsz: Addr,
     pub align: Addr,
+    /// Left out of serialized output, like the data of sections and gaps
+    #[serde(skip)]
     pub data: Vec<u8>,
+    pub contents: SegmentContents,
 }
 
 impl ProgramHeader {
     pub fn file_range(&self) -> Range<Addr> {
-        self.offset..self.offset + self.filesz
+        self.offset.range(self.filesz)
     }
 
     pub fn mem_range(&self) -> Range<Addr> {
-        self.vaddr..self.vaddr + self.memsz
-    }
-
-    pub fn parse<'a>(full_input: parse::Input<'_>, i: parse::Input<'a>) -> parse::Result<'a, Self> {
-        use nom::sequence::tuple;
-        let (i, (r#type, flags)) = tuple((SegmentType::parse, SegmentFlag::parse))(i)?;
-
-        let ap = Addr::parse;
-        let (i, (offset, vaddr, paddr, filesz, memsz, align)) = tuple((ap, ap, ap, ap, ap, ap))(i)?;
-
-        let res = Self {
-            r#type,
-            flags,
-            offset,
-            vaddr,
-            paddr,
-            filesz,
-            memsz,
-            align,
-            data: full_input[offset.into()..][..filesz.into()].to_vec(),
-        };
-
-        Ok((i, res))
+        self.vaddr.range(self.memsz)
     }
 }
 
@@ -155,73 +431,858 @@ impl fmt::Debug for ProgramHeader {
     }
 }
 
-#[derive(Debug)]
+#[derive(Serialize)]
+pub struct SectionHeader {
+    /// Resolved through the section header string table (`.shstrtab`)
+    pub name: String,
+    /// Offset of the name in the section header string table
+    pub name_offset: u32,
+    pub r#type: SectionType,
+    #[serde(serialize_with = "ser::flags")]
+    pub flags: BitFlags<SectionFlag>,
+    /// Flag bits without a `SectionFlag`, such as OS- and
+    /// processor-specific ones
+    pub other_flags: u64,
+    pub addr: Addr,
+    pub offset: Addr,
+    pub size: Addr,
+    pub link: u32,
+    pub info: u32,
+    pub addralign: Addr,
+    pub entsize: Addr,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl SectionHeader {
+    pub fn file_range(&self) -> Range<Addr> {
+        self.offset.range(self.file_size())
+    }
+
+    pub fn mem_range(&self) -> Range<Addr> {
+        self.addr.range(self.size)
+    }
+
+    /// `NoBits` sections (such as `.bss`) take up no space in the file
+    fn file_size(&self) -> Addr {
+        self.r#type.file_size(self.size)
+    }
+
+    /// Returns the null-terminated string starting at `offset`, for
+    /// string table sections such as `.shstrtab`, `.strtab` and `.dynstr`.
+    pub fn get_string(&self, offset: usize) -> Option<String> {
+        let bytes = self.data.get(offset..)?;
+        let len = bytes.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+}
+
+impl fmt::Debug for SectionHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<20} | file {:?} | mem {:?} | {:?} {:?}",
+            self.name,
+            self.file_range(),
+            self.mem_range(),
+            self.flags,
+            self.r#type
+        )
+    }
+}
+
+open_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+    pub enum SymBind: u8 {
+        Local = 0,
+        Global = 1,
+        Weak = 2,
+        GnuUnique = 10,
+    }
+}
+
+open_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+    pub enum SymType: u8 {
+        NoType = 0,
+        Object = 1,
+        Func = 2,
+        Section = 3,
+        File = 4,
+        Common = 5,
+        Tls = 6,
+        GnuIFunc = 10,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, Serialize)]
+#[repr(u8)]
+pub enum SymVisibility {
+    Default = 0,
+    Internal = 1,
+    Hidden = 2,
+    Protected = 3,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SectionIndex(pub u16);
+
+impl SectionIndex {
+    pub const UNDEF: Self = Self(0);
+    pub const ABS: Self = Self(0xfff1);
+    pub const COMMON: Self = Self(0xfff2);
+
+    pub fn is_undef(&self) -> bool {
+        *self == Self::UNDEF
+    }
+
+    /// Reserved indices (0xff00 and up) do not refer to an actual section
+    pub fn is_special(&self) -> bool {
+        self.0 >= 0xff00
+    }
+
+    pub fn get(&self) -> Option<usize> {
+        if self.is_undef() || self.is_special() {
+            None
+        } else {
+            Some(self.0 as usize)
+        }
+    }
+}
+
+impl fmt::Debug for SectionIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::UNDEF => write!(f, "UND"),
+            Self::ABS => write!(f, "ABS"),
+            Self::COMMON => write!(f, "COM"),
+            Self(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct Sym {
+    /// Resolved through the string table linked from the symbol table section
+    pub name: String,
+    pub name_offset: u32,
+    pub bind: SymBind,
+    pub r#type: SymType,
+    pub visibility: SymVisibility,
+    pub shndx: SectionIndex,
+    pub value: Addr,
+    pub size: u64,
+}
+
+impl Sym {
+    pub fn parse(ctx: parse::Context, i: parse::Input) -> parse::Result<Self> {
+        use nom::{
+            combinator::{map, map_res},
+            error::context,
+            number::complete::u8,
+            sequence::tuple,
+        };
+
+        let unknown = |x: u8| parse::ErrorKind::UnknownValue(x as u64);
+        let info = context(
+            "SymInfo",
+            map(u8, |x| (SymBind::from(x >> 4), SymType::from(x & 0xf))),
+        );
+        let visibility = context(
+            "SymVisibility",
+            map_res(u8, |x| {
+                SymVisibility::try_from(x & 0x3).map_err(|_| unknown(x))
+            }),
+        );
+
+        // ELF32 puts value and size right after the name
+        let (i, (name_offset, (bind, r#type), visibility, shndx, value, size)) = match ctx.class {
+            Class::Elf64 => tuple((
+                ctx.u32(),
+                info,
+                visibility,
+                ctx.u16(),
+                Addr::parse(ctx),
+                ctx.u64(),
+            ))(i)?,
+            Class::Elf32 => {
+                let (i, (name_offset, value, size, info, visibility, shndx)) =
+                    tuple((
+                        ctx.u32(),
+                        Addr::parse(ctx),
+                        ctx.word(),
+                        info,
+                        visibility,
+                        ctx.u16(),
+                    ))(i)?;
+                (i, (name_offset, info, visibility, shndx, value, size))
+            }
+        };
+
+        let res = Self {
+            name: String::new(),
+            name_offset,
+            bind,
+            r#type,
+            visibility,
+            shndx: SectionIndex(shndx),
+            value,
+            size,
+        };
+        Ok((i, res))
+    }
+
+    pub fn mem_range(&self) -> Range<Addr> {
+        self.value.range(Addr(self.size))
+    }
+}
+
+impl fmt::Debug for Sym {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} | size {:<6} | {:?} {:?} {:?} | ndx {:?} | {}",
+            self.value, self.size, self.bind, self.r#type, self.visibility, self.shndx, self.name
+        )
+    }
+}
+
+/// Defined symbols sorted by address, to find the ones addresses belong to
+pub struct SymbolMap {
+    syms: Vec<Sym>,
+    /// Memory ranges of the allocated sections, by section index
+    sections: Vec<Option<Range<Addr>>>,
+}
+
+impl SymbolMap {
+    /// Finds the symbol an address belongs to, along with the offset of the
+    /// address from the start of that symbol.
+    ///
+    /// Symbols with a size must contain the address. Symbols without one
+    /// (common for hand-written assembly) match the closest preceding
+    /// address in the same section. Of several symbols at the same address,
+    /// the first one with a size wins.
+    pub fn lookup(&self, addr: Addr) -> Option<(&Sym, u64)> {
+        let section = self
+            .sections
+            .iter()
+            .position(|range| range.as_ref().is_some_and(|r| r.contains(&addr)));
+        let matches = |sym: &Sym| {
+            if sym.size > 0 {
+                sym.mem_range().contains(&addr)
+            } else {
+                sym.shndx.get() == section
+            }
+        };
+
+        let end = self.syms.partition_point(|sym| sym.value <= addr);
+        let value = self.syms[..end]
+            .iter()
+            .rev()
+            .find(|sym| matches(sym))?
+            .value;
+        let start = self.syms.partition_point(|sym| sym.value < value);
+        let sym = self.syms[start..end]
+            .iter()
+            .filter(|sym| matches(sym))
+            .reduce(|best, sym| if best.size == 0 { sym } else { best })?;
+        Some((sym, (addr - sym.value).0))
+    }
+
+    /// Symbols starting at `addr`
+    pub fn at(&self, addr: Addr) -> &[Sym] {
+        let start = self.syms.partition_point(|sym| sym.value < addr);
+        let end = self.syms.partition_point(|sym| sym.value <= addr);
+        &self.syms[start..end]
+    }
+}
+
+open_enum! {
+    /// x86-64 relocation types: other machines number theirs differently, so
+    /// all of theirs are `Other`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+    #[allow(non_camel_case_types)]
+    pub enum RelType: u32 {
+        None = 0,
+        _64 = 1,
+        Pc32 = 2,
+        Got32 = 3,
+        Plt32 = 4,
+        Copy = 5,
+        GlobDat = 6,
+        JumpSlot = 7,
+        Relative = 8,
+        GotPcRel = 9,
+        _32 = 10,
+        _32S = 11,
+        _16 = 12,
+        Pc16 = 13,
+        _8 = 14,
+        Pc8 = 15,
+        DtpMod64 = 16,
+        DtpOff64 = 17,
+        TpOff64 = 18,
+        TlsGd = 19,
+        TlsLd = 20,
+        DtpOff32 = 21,
+        GotTpOff = 22,
+        TpOff32 = 23,
+        Pc64 = 24,
+        GotOff64 = 25,
+        GotPc32 = 26,
+        Size32 = 32,
+        Size64 = 33,
+        GotPc32TlsDesc = 34,
+        TlsDescCall = 35,
+        TlsDesc = 36,
+        IRelative = 37,
+        GotPcRelX = 41,
+        RexGotPcRelX = 42,
+    }
+}
+
+#[derive(Serialize)]
+pub struct Rela {
+    pub offset: Addr,
+    pub r#type: RelType,
+    /// Index into the symbol table linked to the relocation table
+    pub sym: u32,
+    /// Signed, stored as two's complement
+    pub addend: Addr,
+}
+
+impl Rela {
+    pub fn parse(ctx: parse::Context, machine: Machine, i: parse::Input) -> parse::Result<Self> {
+        use nom::{combinator::map, error::context};
+
+        let (i, offset) = Addr::parse(ctx)(i)?;
+        let (i, (r#type, sym)) = context(
+            "RelType",
+            map(ctx.word(), |x| {
+                // ELF32 packs the symbol index and type as 24 and 8 bits
+                let (r#type, sym) = match ctx.class {
+                    Class::Elf64 => (x & 0xffffffff, x >> 32),
+                    Class::Elf32 => (x & 0xff, x >> 8),
+                };
+                let r#type = match machine {
+                    Machine::X86_64 => RelType::from(r#type as u32),
+                    _ => RelType::Other(r#type as u32),
+                };
+                (r#type, sym as u32)
+            }),
+        )(i)?;
+        let (i, addend) = map(ctx.sword(), Addr)(i)?;
+
+        let res = Self {
+            offset,
+            r#type,
+            sym,
+            addend,
+        };
+        Ok((i, res))
+    }
+
+    /// Size of an `Elf32_Rela` or `Elf64_Rela` entry
+    pub fn size(class: Class) -> usize {
+        match class {
+            Class::Elf32 => 12,
+            Class::Elf64 => 24,
+        }
+    }
+}
+
+impl fmt::Debug for Rela {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} | {:?} | sym {} | addend {:#x}",
+            self.offset, self.r#type, self.sym, self.addend.0 as i64
+        )
+    }
+}
+
+fn read_rela_table(
+    ctx: parse::Context,
+    machine: Machine,
+    table: &[u8],
+    start: Addr,
+    entsize: usize,
+) -> Result<Vec<Rela>, Error> {
+    table
+        .chunks(entsize.max(1))
+        .enumerate()
+        .map(|(n, chunk)| {
+            Rela::parse(ctx, machine, chunk)
+                .map(|(_, rela)| rela)
+                .map_err(|_| Error::InvalidRela(Addr(start.0.saturating_add((n * entsize) as u64))))
+        })
+        .collect()
+}
+
+/// Decodes a `DT_RELR` table: each entry is either the (even) address of a
+/// word to relocate or, when its lowest bit is set, a bitmap of which of
+/// the words that follow are to be relocated too
+fn read_relr_table(ctx: parse::Context, table: &[u8]) -> Vec<Addr> {
+    let word_size = match ctx.class {
+        Class::Elf32 => 4,
+        Class::Elf64 => 8,
+    };
+    let mut res = Vec::new();
+    let mut next = Addr(0);
+    for chunk in table.chunks_exact(word_size as usize) {
+        let entry = match Addr::parse(ctx)(chunk) {
+            Ok((_, entry)) => entry.0,
+            Err(_) => break,
+        };
+        if entry & 1 == 0 {
+            res.push(Addr(entry));
+            next = Addr(entry.wrapping_add(word_size));
+            continue;
+        }
+        let bits = word_size * 8 - 1;
+        for bit in 0..bits {
+            if entry >> (bit + 1) & 1 == 1 {
+                res.push(Addr(next.0.wrapping_add(bit * word_size)));
+            }
+        }
+        next = Addr(next.0.wrapping_add(bits * word_size));
+    }
+    res
+}
+
+#[derive(Debug, Serialize)]
 pub struct File {
+    pub class: Class,
+    pub endianness: Endianness,
+    pub os_abi: u8,
+    pub abi_version: u8,
+    /// The rest of `e_ident`, reserved and normally zero
+    pub padding: [u8; 7],
     pub r#type: Type,
     pub machine: Machine,
     pub entry_point: Addr,
+    /// Processor-specific flags
+    pub flags: u32,
+    /// Sizes of the ELF header and of header table entries, as recorded in
+    /// the ELF header. Entries larger than the class's own are padded.
+    pub hdr_size: u16,
+    pub ph_offset: Addr,
+    pub ph_entsize: u16,
+    pub sh_offset: Addr,
+    pub sh_entsize: u16,
+    /// Index of the section header string table (`.shstrtab`)
+    pub sh_nidx: u16,
     pub program_headers: Vec<ProgramHeader>,
+    pub section_headers: Vec<SectionHeader>,
+    /// File contents not covered by the headers, segments or sections, such
+    /// as alignment padding, kept so that writing the file reproduces it
+    pub gaps: Vec<Gap>,
+}
+
+#[derive(Serialize)]
+pub struct Gap {
+    pub offset: Addr,
+    #[serde(rename = "size", serialize_with = "ser::len")]
+    pub data: Vec<u8>,
+}
+
+impl Gap {
+    pub fn file_range(&self) -> Range<Addr> {
+        self.offset.range(Addr(self.data.len() as u64))
+    }
+}
+
+impl fmt::Debug for Gap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "file {:?}", self.file_range())
+    }
 }
 
 impl File {
-    const MAGIC: &'static [u8] = &[0x7f, 0x45, 0x4c, 0x46];
+    pub(crate) const MAGIC: &'static [u8] = &[0x7f, 0x45, 0x4c, 0x46];
 
-    #[allow(unused_variables)]
     pub fn parse(i: parse::Input) -> parse::Result<Self> {
-        use nom::{
-            bytes::complete::{tag, take},
-            combinator::{map, verify},
-            error::context,
-            number::complete::{le_u16, le_u32},
-            sequence::tuple,
-        };
+        let (i, file) = FileRef::parse(i)?;
+        Ok((i, Self::from_ref(&file)?))
+    }
 
-        let full_input = i;
-
-        let (i, _) = tuple((
-            context("Magic", tag(Self::MAGIC)),
-            context("Class", tag(&[0x2])),
-            context("Endianess", tag(&[0x1])),
-            context("Version", tag(&[0x1])),
-            context("OS ABI", nom::branch::alt((tag(&[0x0]), tag(&[0x3])))),
-            context("Padding", take(8_usize)),
-        ))(i)?;
-
-        let (i, (r#type, machine)) = tuple((Type::parse, Machine::parse))(i)?;
-        let (i, _) = context("Version (bis)", verify(le_u32, |&x| x == 1))(i)?;
-        let (i, entry_point) = Addr::parse(i)?;
-
-        // ph = program header, sh = section header
-        let (i, (ph_offset, sh_offset)) = tuple((Addr::parse, Addr::parse))(i)?;
-        let (i, (flags, hdr_size)) = tuple((le_u32, le_u16))(i)?;
-
-        let (i, (ph_entsize, ph_count)) =
-            tuple((map(le_u16, |x| x as usize), map(le_u16, |x| x as usize)))(i)?;
-        let (i, (sh_entsize, sh_count, sh_nidx)) = tuple((
-            map(le_u16, |x| x as usize),
-            map(le_u16, |x| x as usize),
-            map(le_u16, |x| x as usize),
-        ))(i)?;
-
-        let ph_slices = (&full_input[ph_offset.into()..]).chunks(ph_entsize);
+    /// Copies everything out of `file`, parsing all headers along the way.
+    /// Segments whose contents fail to decode are kept as
+    /// `SegmentContents::Unknown`, like the notes `notes` skips.
+    pub(crate) fn from_ref<'a>(file: &FileRef<'a>) -> file_ref::ReadResult<'a, Self> {
         let mut program_headers = Vec::new();
+        for index in 0..file.ph_count as usize {
+            let ph = file.read_program_header(index)?;
+            let contents = file
+                .read_segment_contents(&ph)
+                .unwrap_or(SegmentContents::Unknown);
+            program_headers.push(ph.into_owned(contents));
+        }
 
-        for ph_slice in ph_slices.take(ph_count) {
-            let (_, ph) = ProgramHeader::parse(full_input, ph_slice)?;
-            program_headers.push(ph);
+        let mut section_headers = Vec::new();
+        for index in 0..file.sh_count as usize {
+            section_headers.push(file.read_section_header(index)?.into_owned());
         }
 
-        let res = Self {
-            machine,
-            r#type,
-            entry_point,
+        // only the parsed part of the header and of each table entry is
+        // covered, so that any padding past it ends up in `gaps`
+        let class = file.class;
+        let entries = |offset: Addr, entsize: u16, size: u16, count: usize| {
+            (0..count as u64).map(move |index| {
+                (offset + Addr(entsize as u64 * index)).range(Addr(size.min(entsize) as u64))
+            })
+        };
+        let mut covered = vec![Addr(0)..Addr(class.header_size() as u64)];
+        covered.extend(entries(
+            file.ph_offset,
+            file.ph_entsize,
+            class.program_header_size(),
+            program_headers.len(),
+        ));
+        covered.extend(entries(
+            file.sh_offset,
+            file.sh_entsize,
+            class.section_header_size(),
+            section_headers.len(),
+        ));
+        covered.extend(program_headers.iter().map(|ph| ph.file_range()));
+        covered.extend(section_headers.iter().map(|sh| sh.file_range()));
+        covered.sort_by_key(|r| r.start);
+        // an empty range at the end picks up any trailing bytes
+        let end = Addr(file.input.len() as u64);
+        covered.push(end..end);
+
+        let mut gaps = Vec::new();
+        let mut pos = Addr(0);
+        for range in &covered {
+            // `NoBits` sections and empty tables can start past the end
+            let start = range.start.min(end);
+            if start > pos {
+                gaps.push(Gap {
+                    offset: pos,
+                    data: file.input[pos.into()..start.into()].to_vec(),
+                });
+            }
+            pos = pos.max(range.end);
+        }
+
+        Ok(Self {
+            class: file.class,
+            endianness: file.endianness,
+            os_abi: file.os_abi,
+            abi_version: file.abi_version,
+            padding: file.padding,
+            r#type: file.r#type,
+            machine: file.machine,
+            entry_point: file.entry_point,
+            flags: file.flags,
+            hdr_size: file.hdr_size,
+            ph_offset: file.ph_offset,
+            ph_entsize: file.ph_entsize,
+            sh_offset: file.sh_offset,
+            sh_entsize: file.sh_entsize,
+            sh_nidx: file.sh_nidx,
             program_headers,
+            section_headers,
+            gaps,
+        })
+    }
+
+    fn context(&self) -> parse::Context {
+        parse::Context {
+            class: self.class,
+            endianness: self.endianness,
+        }
+    }
+
+    pub fn section_by_name(&self, name: &str) -> Option<&SectionHeader> {
+        self.section_headers.iter().find(|sh| sh.name == name)
+    }
+
+    /// Symbols from `.symtab`, empty for stripped files
+    pub fn symbols(&self) -> impl Iterator<Item = Sym> + '_ {
+        self.read_symbols(SectionType::SymTab)
+    }
+
+    /// Symbols from `.dynsym`, the ones used for dynamic linking
+    pub fn dynamic_symbols(&self) -> impl Iterator<Item = Sym> + '_ {
+        self.read_symbols(SectionType::DynSym)
+    }
+
+    fn read_symbols(&self, r#type: SectionType) -> impl Iterator<Item = Sym> + '_ {
+        self.section_headers
+            .iter()
+            .filter(move |sh| sh.r#type == r#type)
+            .flat_map(move |sh| self.section_symbols(sh))
+    }
+
+    /// Symbols of a `SymTab` or `DynSym` section, named from the string
+    /// table it links to. Entries only fail to parse when they are too short,
+    /// and then end the iteration: skipping one would shift the index of
+    /// every later symbol, which relocations refer to them by.
+    pub fn section_symbols<'a>(&'a self, sh: &'a SectionHeader) -> impl Iterator<Item = Sym> + 'a {
+        let strtab = self.section_headers.get(sh.link as usize);
+        let ctx = self.context();
+        let entsize = match sh.entsize {
+            // no entries, rather than a panic
+            Addr(0) => sh.data.len() + 1,
+            entsize => entsize.into(),
         };
-        Ok((i, res))
+        sh.data
+            .chunks_exact(entsize)
+            .map_while(move |chunk| Sym::parse(ctx, chunk).ok())
+            .map(move |(_, mut sym)| {
+                if let Some(name) = strtab.and_then(|s| s.get_string(sym.name_offset as usize)) {
+                    sym.name = name;
+                }
+                sym
+            })
+    }
+
+    /// Finds the symbol an address belongs to, along with the offset of the
+    /// address from the start of that symbol. See `SymbolMap::lookup`, which
+    /// is faster for many lookups.
+    pub fn symbol_for_addr(&self, addr: Addr) -> Option<(Sym, u64)> {
+        self.symbol_map()
+            .lookup(addr)
+            .map(|(sym, offset)| (sym.clone(), offset))
+    }
+
+    /// Sorts the symbols of `.symtab` and `.dynsym` by address. Thread-local
+    /// symbols are left out: their values are offsets in the TLS block.
+    pub fn symbol_map(&self) -> SymbolMap {
+        let mut syms = self
+            .symbols()
+            .chain(self.dynamic_symbols())
+            .filter(|sym| {
+                sym.shndx.get().is_some()
+                    && !matches!(sym.r#type, SymType::Section | SymType::File | SymType::Tls)
+            })
+            .collect::<Vec<_>>();
+        // stable, so that ties keep the order of the tables
+        syms.sort_by_key(|sym| sym.value);
+        let sections = self
+            .section_headers
+            .iter()
+            .map(|sh| {
+                if sh.flags.contains(SectionFlag::Alloc) {
+                    Some(sh.mem_range())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        SymbolMap { syms, sections }
+    }
+
+    pub fn segment_of_type(&self, r#type: SegmentType) -> Option<&ProgramHeader> {
+        self.program_headers.iter().find(|ph| ph.r#type == r#type)
+    }
+
+    /// Returns the `Load` segment `addr` is mapped into
+    pub fn segment_at(&self, addr: Addr) -> Option<&ProgramHeader> {
+        self.program_headers
+            .iter()
+            .filter(|ph| ph.r#type == SegmentType::Load)
+            .find(|ph| ph.mem_range().contains(&addr))
+    }
+
+    /// Returns the file contents mapped at `addr`, up to the end of the
+    /// segment's file data
+    pub fn slice_at(&self, addr: Addr) -> Option<&[u8]> {
+        let ph = self.segment_at(addr)?;
+        ph.data.get((addr - ph.vaddr).into()..)
+    }
+
+    /// Notes from `PT_NOTE` segments, or from `SHT_NOTE` sections for files
+    /// without segments, such as relocatable objects. Sections whose notes
+    /// fail to parse are skipped.
+    pub fn notes(&self) -> Vec<Note> {
+        if !self.program_headers.is_empty() {
+            return self
+                .program_headers
+                .iter()
+                .flat_map(|ph| match &ph.contents {
+                    SegmentContents::Note(notes) => notes.clone(),
+                    _ => Vec::new(),
+                })
+                .collect();
+        }
+        self.section_headers
+            .iter()
+            .filter(|sh| sh.r#type == SectionType::Note)
+            .filter_map(|sh| {
+                Note::parse_all(self.context(), self.machine, sh.addralign, &sh.data).ok()
+            })
+            .flat_map(|(_, notes)| notes)
+            .collect()
+    }
+
+    /// The `NT_GNU_BUILD_ID` note, which debuggers use to find separate
+    /// debug symbols
+    pub fn build_id(&self) -> Option<BuildId> {
+        self.notes().into_iter().find_map(|note| match note {
+            Note::GnuBuildId(id) => Some(id),
+            _ => None,
+        })
+    }
+
+    pub fn dynamic_table(&self) -> Option<&[DynamicEntry]> {
+        match self.segment_of_type(SegmentType::Dynamic) {
+            Some(ProgramHeader {
+                contents: SegmentContents::Dynamic(entries),
+                ..
+            }) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub fn dynamic_entries(&self, tag: DynamicTag) -> impl Iterator<Item = Addr> + '_ {
+        self.dynamic_table()
+            .unwrap_or_default()
+            .iter()
+            .filter(move |e| e.tag == tag)
+            .map(|e| e.addr)
+    }
+
+    pub fn dynamic_entry(&self, tag: DynamicTag) -> Option<Addr> {
+        self.dynamic_entries(tag).next()
+    }
+
+    /// Reads a string from the dynamic string table (`DT_STRTAB`)
+    pub fn dynamic_string(&self, offset: Addr) -> Option<String> {
+        let strtab = self.dynamic_entry(DynamicTag::StrTab)?;
+        let bytes = self.slice_at(Addr(strtab.0.checked_add(offset.0)?))?;
+        let len = bytes.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+
+    /// Names of the shared libraries this file depends on (`DT_NEEDED`),
+    /// in the order they should be loaded
+    pub fn needed_libraries(&self) -> Vec<String> {
+        self.dynamic_entries(DynamicTag::Needed)
+            .filter_map(|offset| self.dynamic_string(offset))
+            .collect()
+    }
+
+    pub fn soname(&self) -> Option<String> {
+        self.dynamic_string(self.dynamic_entry(DynamicTag::SoName)?)
+    }
+
+    pub fn rpath(&self) -> Option<String> {
+        self.dynamic_string(self.dynamic_entry(DynamicTag::RPath)?)
+    }
+
+    pub fn runpath(&self) -> Option<String> {
+        self.dynamic_string(self.dynamic_entry(DynamicTag::RunPath)?)
+    }
+
+    /// Whether the object asks for all its symbols to be bound at load time
+    /// rather than on first call, with `DT_BIND_NOW`, `DF_BIND_NOW` or
+    /// `DF_1_NOW`
+    pub fn bind_now(&self) -> bool {
+        const DF_BIND_NOW: u64 = 0x8;
+        const DF_1_NOW: u64 = 0x1;
+        let has_flag = |tag, flag| self.dynamic_entries(tag).any(|x| x.0 & flag != 0);
+        self.dynamic_entry(DynamicTag::BindNow).is_some()
+            || has_flag(DynamicTag::Flags, DF_BIND_NOW)
+            || has_flag(DynamicTag::Flags1, DF_1_NOW)
+    }
+
+    /// Whether a `Dyn` file is a position-independent executable rather than
+    /// a shared library: from `DF_1_PIE`, or for older linkers that don't
+    /// set it, from asking for an interpreter. Some libraries do too, to be
+    /// runnable, like libc.so.6, but they have a soname.
+    pub fn is_pie(&self) -> bool {
+        self.r#type == Type::Dyn
+            && (self
+                .dynamic_entries(DynamicTag::Flags1)
+                .any(|flags| flags.0 & DF_1_PIE != 0)
+                || (self.segment_of_type(SegmentType::Interp).is_some()
+                    && self.dynamic_entry(DynamicTag::SoName).is_none()))
+    }
+
+    /// Whether relocations write to segments that are not writable, with
+    /// `DT_TEXTREL` or `DF_TEXTREL`
+    pub fn has_text_relocations(&self) -> bool {
+        const DF_TEXTREL: u64 = 0x4;
+        self.dynamic_entry(DynamicTag::TextRel).is_some()
+            || self
+                .dynamic_entries(DynamicTag::Flags)
+                .any(|x| x.0 & DF_TEXTREL != 0)
+    }
+
+    /// Relocations from the `DT_RELA` table, applied at load time
+    pub fn rela_entries(&self) -> Result<Vec<Rela>, Error> {
+        self.read_dynamic_rela(DynamicTag::Rela, DynamicTag::RelaSz)
+    }
+
+    /// Relocations from the `DT_JMPREL` table, for PLT entries that may be
+    /// bound lazily
+    pub fn plt_rela_entries(&self) -> Result<Vec<Rela>, Error> {
+        match self.dynamic_entry(DynamicTag::PltRel) {
+            // DT_PLTREL is either DT_RELA or DT_REL
+            Some(Addr(x)) if DynamicTag::from(x) != DynamicTag::Rela => Ok(Vec::new()),
+            _ => self.read_dynamic_rela(DynamicTag::JmpRel, DynamicTag::PltRelSz),
+        }
+    }
+
+    /// Addresses of the words to relocate by adding the base address, from
+    /// the packed `DT_RELR` table that stands in for most `Relative`
+    /// relocations in recent toolchains
+    pub fn relr_entries(&self) -> Result<Vec<Addr>, Error> {
+        let (_, table) = self.dynamic_table_data(DynamicTag::Relr, DynamicTag::RelrSz)?;
+        Ok(read_relr_table(self.context(), table))
+    }
+
+    fn read_dynamic_rela(
+        &self,
+        addr_tag: DynamicTag,
+        size_tag: DynamicTag,
+    ) -> Result<Vec<Rela>, Error> {
+        let (addr, table) = self.dynamic_table_data(addr_tag, size_tag)?;
+        let entsize = self
+            .dynamic_entry(DynamicTag::RelaEnt)
+            .map(usize::from)
+            .unwrap_or_else(|| Rela::size(self.class));
+        read_rela_table(self.context(), self.machine, table, addr, entsize)
+    }
+
+    /// Contents of a table the dynamic section gives the address and size
+    /// of, empty if it has neither
+    fn dynamic_table_data(
+        &self,
+        addr_tag: DynamicTag,
+        size_tag: DynamicTag,
+    ) -> Result<(Addr, &[u8]), Error> {
+        let (addr, size) = match (self.dynamic_entry(addr_tag), self.dynamic_entry(size_tag)) {
+            (None, None) => return Ok((Addr(0), &[])),
+            (Some(addr), Some(size)) => (addr, size),
+            (None, Some(_)) => return Err(Error::MissingDynamicEntry(addr_tag)),
+            (Some(_), None) => return Err(Error::MissingDynamicEntry(size_tag)),
+        };
+        let table = self
+            .slice_at(addr)
+            .and_then(|data| data.get(..size.into()))
+            .ok_or(Error::SegmentNotFound(addr))?;
+        Ok((addr, table))
+    }
+
+    /// Relocations from a `Rela` section such as `.rela.text`, as found in
+    /// relocatable object files
+    pub fn section_rela_entries(&self, sh: &SectionHeader) -> Result<Vec<Rela>, Error> {
+        if sh.r#type != SectionType::Rela {
+            return Ok(Vec::new());
+        }
+        let entsize = match sh.entsize {
+            Addr(0) => Rela::size(self.class),
+            entsize => entsize.into(),
+        };
+        read_rela_table(self.context(), self.machine, &sh.data, sh.offset, entsize)
     }
 }
 
-pub struct HexDump<'a>(&'a [u8]);
+pub struct HexDump<'a>(pub &'a [u8]);
 
 impl<'a> fmt::Debug for HexDump<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -233,21 +1294,12 @@ impl<'a> fmt::Debug for HexDump<'a> {
 }
 
 impl File {
-    pub fn parse_or_print_error(i: parse::Input) -> Option<Self> {
-        match Self::parse(i) {
-            Ok((_, file)) => Some(file),
-            Err(nom::Err::Failure(err)) | Err(nom::Err::Error(err)) => {
-                eprintln!("Parsing failed:");
-                for (input, err) in err.errors {
-                    use nom::Offset;
-                    let offset = i.offset(input);
-                    eprintln!("{:?} at position: {}", err, offset);
-                    eprintln!("{:?}", HexDump(input));
-                }
-                None
-            }
-            Err(_) => panic!("Unexpected nom error"),
-        }
+    /// Parses a whole ELF file, turning parser failures into an `Error`
+    /// that says what was wrong with the input
+    pub fn from_bytes(i: parse::Input) -> Result<Self, Error> {
+        Self::parse(i)
+            .map(|(_, file)| file)
+            .map_err(|err| Error::from_nom(i, err))
     }
 }
 
@@ -268,9 +1320,23 @@ mod tests {
 
     #[test]
     fn try_enums() {
-        assert_eq!(Machine::X86_64 as u16, 0x3E);
-        assert_eq!(Machine::try_from(0x3E), Ok(Machine::X86_64));
-        assert_eq!(Machine::try_from(0xFA), Err(0xFA));
+        assert_eq!(u16::from(Machine::X86_64), 0x3E);
+        assert_eq!(Machine::from(0x3E), Machine::X86_64);
+        assert_eq!(Machine::from(0xFA), Machine::Other(0xFA));
+        assert_eq!(u16::from(Machine::Other(0xFA)), 0xFA);
+    }
+
+    #[test]
+    fn any_os_abi() {
+        use super::File;
+        // FreeBSD, ARM and standalone
+        for os_abi in [9, 0x61, 0xff] {
+            let mut input = include_bytes!("../../elk/samples/hello").to_vec();
+            input[7] = os_abi;
+            let file = File::from_bytes(&input).unwrap();
+            assert_eq!(file.os_abi, os_abi);
+            assert_eq!(file.to_bytes(), input);
+        }
     }
 
     #[test]
@@ -284,4 +1350,318 @@ mod tests {
 
         assert!(BitFlags::<SegmentFlag>::from_bits(1992).is_err());
     }
+
+    #[test]
+    fn section_headers() {
+        use super::{Addr, File, SectionFlag, SectionType};
+        let input = include_bytes!("../../elk/samples/hello");
+        let (_, file) = File::parse(&input[..]).unwrap();
+
+        let names = file
+            .section_headers
+            .iter()
+            .map(|sh| sh.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["", ".text", ".data", ".symtab", ".strtab", ".shstrtab"]
+        );
+
+        let text = file.section_by_name(".text").unwrap();
+        assert_eq!(text.r#type, SectionType::ProgBits);
+        assert_eq!(text.flags, SectionFlag::Alloc | SectionFlag::ExecInstr);
+        assert_eq!(text.addr, file.entry_point);
+        assert_eq!(text.size, Addr(0x25));
+
+        let data = file.section_by_name(".data").unwrap();
+        assert_eq!(data.data, b"hi there\n");
+
+        let symtab = file.section_by_name(".symtab").unwrap();
+        assert_eq!(symtab.r#type, SectionType::SymTab);
+        assert_eq!(symtab.entsize, Addr(0x18));
+        assert!(file.section_by_name(".bss").is_none());
+    }
+
+    #[test]
+    fn symbols() {
+        use super::{Addr, File, SymBind, SymType};
+        let input = include_bytes!("../../elk/samples/hello");
+        let (_, file) = File::parse(&input[..]).unwrap();
+
+        let start = file.symbols().find(|sym| sym.name == "_start").unwrap();
+        assert_eq!(start.value, file.entry_point);
+        assert_eq!(start.bind, SymBind::Global);
+        assert_eq!(file.dynamic_symbols().count(), 0);
+
+        let (sym, offset) = file.symbol_for_addr(Addr(0x401010)).unwrap();
+        assert_eq!((sym.name.as_str(), offset), ("_start", 0x10));
+        let (sym, offset) = file.symbol_for_addr(Addr(0x402004)).unwrap();
+        assert_eq!((sym.name.as_str(), offset), ("msg", 0x4));
+        let map = file.symbol_map();
+        assert!(map
+            .at(file.entry_point)
+            .iter()
+            .any(|sym| sym.name == "_start"));
+        assert!(map.at(file.entry_point + Addr(1)).is_empty());
+        assert!(map.lookup(Addr(0x400fff)).is_none());
+
+        let input = include_bytes!("../../elk/samples/entrypoint");
+        let (_, file) = File::parse(&input[..]).unwrap();
+
+        let main = file.symbols().find(|sym| sym.name == "main").unwrap();
+        assert_eq!(main.r#type, SymType::Func);
+        assert!(main.size > 0);
+        let (sym, _) = file.symbol_for_addr(main.value + Addr(1)).unwrap();
+        assert_eq!(sym.name, "main");
+
+        assert!(file
+            .dynamic_symbols()
+            .any(|sym| sym.name == "__printf_chk" && sym.shndx.is_undef()));
+    }
+
+    #[test]
+    fn dynamic_entries() {
+        use super::{Addr, DynamicTag, File};
+        let input = include_bytes!("../../elk/samples/hello");
+        let (_, file) = File::parse(&input[..]).unwrap();
+        assert!(file.dynamic_table().is_none());
+        assert!(file.needed_libraries().is_empty());
+
+        let input = include_bytes!("../../elk/samples/entrypoint");
+        let (_, file) = File::parse(&input[..]).unwrap();
+        let table = file.dynamic_table().unwrap();
+        assert_eq!(table.len(), 28);
+        assert_eq!(table.last().unwrap().tag, DynamicTag::Null);
+
+        assert_eq!(file.dynamic_entry(DynamicTag::Init), Some(Addr(0x401000)));
+        assert_eq!(file.dynamic_entry(DynamicTag::RelaSz), Some(Addr(48)));
+        assert_eq!(file.needed_libraries(), ["libc.so.6"]);
+        assert!(file.runpath().unwrap().ends_with("gcc-9.2.0-lib/lib"));
+        assert!(file.rpath().is_none());
+        assert!(file.bind_now());
+
+        let input = include_bytes!("../../elk/samples/greet");
+        let (_, file) = File::parse(&input[..]).unwrap();
+        assert!(!file.bind_now());
+    }
+
+    #[test]
+    fn unknown_dynamic_tag() {
+        use super::{DynamicTag, File, SegmentType};
+        let mut input = include_bytes!("../../elk/samples/entrypoint").to_vec();
+        let file = File::from_bytes(&input).unwrap();
+        let dynamic = file.segment_of_type(SegmentType::Dynamic).unwrap();
+        // DT_DEBUG becomes DT_MIPS_RLD_VERSION
+        let at = usize::from(dynamic.offset) + 14 * 16;
+        input[at..at + 8].copy_from_slice(&0x7000_0001_u64.to_le_bytes());
+
+        let file = File::from_bytes(&input).unwrap();
+        let table = file.dynamic_table().unwrap();
+        assert_eq!(table[14].tag, DynamicTag::Other(0x7000_0001));
+        assert!(file
+            .readelf()
+            .to_string()
+            .contains("(Processor Specific: 70000001)"));
+        assert_eq!(file.to_bytes(), input);
+    }
+
+    #[test]
+    fn text_relocations() {
+        use super::{DynamicTag, File, SegmentType};
+        let mut input = include_bytes!("../../elk/samples/entrypoint").to_vec();
+        let file = File::from_bytes(&input).unwrap();
+        assert!(!file.has_text_relocations());
+        // DT_DEBUG becomes DT_TEXTREL
+        let dynamic = file.segment_of_type(SegmentType::Dynamic).unwrap();
+        let at = usize::from(dynamic.offset) + 14 * 16;
+        input[at..at + 8].copy_from_slice(&u64::from(DynamicTag::TextRel).to_le_bytes());
+
+        let file = File::from_bytes(&input).unwrap();
+        assert!(file.has_text_relocations());
+    }
+
+    #[test]
+    fn os_specific_symbols() {
+        use super::{File, SymBind, SymType};
+        let mut input = include_bytes!("../../elk/samples/entrypoint").to_vec();
+        let file = File::from_bytes(&input).unwrap();
+        let names = |file: &File| {
+            file.dynamic_symbols()
+                .map(|sym| sym.name)
+                .collect::<Vec<_>>()
+        };
+        let before = names(&file);
+        // st_info of the first symbol after the null one: STB_LOPROC,
+        // STT_LOOS + 1
+        let dynsym = file.section_by_name(".dynsym").unwrap();
+        let at = usize::from(dynsym.offset) + 24 + 4;
+        input[at] = 13 << 4 | 11;
+
+        let file = File::from_bytes(&input).unwrap();
+        assert_eq!(names(&file), before);
+        let sym = file.dynamic_symbols().nth(1).unwrap();
+        assert_eq!(sym.bind, SymBind::Other(13));
+        assert_eq!(sym.r#type, SymType::Other(11));
+        let readelf = file.readelf().to_string();
+        assert!(readelf.contains("<OS specific>: 11 <processor specific>: 13"));
+        assert_eq!(file.to_bytes(), input);
+    }
+
+    #[test]
+    fn relr_table() {
+        use super::{parse, read_relr_table, Addr, Class, Endianness};
+        let ctx = parse::Context {
+            class: Class::Elf64,
+            endianness: Endianness::Little,
+        };
+        let mut table = Vec::new();
+        // 0x1000, then a bitmap of the 63 words after it: the first, the
+        // second and the last, then the first of the 63 after those
+        for entry in [0x1000_u64, 0b111 | 1 << 63, 0b11] {
+            table.extend_from_slice(&entry.to_le_bytes());
+        }
+        assert_eq!(
+            read_relr_table(ctx, &table),
+            [
+                Addr(0x1000),
+                Addr(0x1008),
+                Addr(0x1010),
+                Addr(0x1008 + 62 * 8),
+                Addr(0x1008 + 63 * 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn rela_entries() {
+        use super::{Addr, File, RelType};
+        let input = include_bytes!("../../elk/samples/entrypoint");
+        let (_, file) = File::parse(&input[..]).unwrap();
+
+        let rela = file.rela_entries().unwrap();
+        assert_eq!(rela.len(), 2);
+        assert_eq!(rela[0].offset, Addr(0x403ff0));
+        assert_eq!(rela[0].r#type, RelType::GlobDat);
+        assert_eq!(rela[1].sym, 2);
+
+        let plt = file.plt_rela_entries().unwrap();
+        assert_eq!(plt.len(), 1);
+        assert_eq!(plt[0].r#type, RelType::JumpSlot);
+        assert_eq!(plt[0].sym, 3);
+
+        let input = include_bytes!("../../elk/samples/hello.o");
+        let (_, file) = File::parse(&input[..]).unwrap();
+        assert!(file.program_headers.is_empty());
+        assert!(file.rela_entries().unwrap().is_empty());
+
+        let sh = file.section_by_name(".rela.text").unwrap();
+        let rela = file.section_rela_entries(sh).unwrap();
+        assert_eq!(rela.len(), 1);
+        assert_eq!(rela[0].offset, Addr(7));
+        assert_eq!(rela[0].r#type, RelType::_64);
+    }
+
+    #[test]
+    fn rela_entries_of_other_machines() {
+        use super::{File, Machine, RelType};
+        let mut input = include_bytes!("../../elk/samples/entrypoint").to_vec();
+        // e_machine: AArch64, whose 6 is not R_X86_64_GLOB_DAT
+        input[18..20].copy_from_slice(&u16::from(Machine::AArch64).to_le_bytes());
+
+        let file = File::from_bytes(&input).unwrap();
+        let rela = file.rela_entries().unwrap();
+        assert_eq!(rela[0].r#type, RelType::Other(6));
+        assert_eq!(rela[1].sym, 2);
+    }
+
+    #[test]
+    fn elf32() {
+        use super::{Addr, Class, Endianness, File, Machine, SegmentFlag};
+        let input = include_bytes!("../../elk/samples/hello32");
+        let (_, file) = File::parse(&input[..]).unwrap();
+
+        assert_eq!(file.class, Class::Elf32);
+        assert_eq!(file.endianness, Endianness::Little);
+        assert_eq!(file.machine, Machine::X86);
+        assert_eq!(file.entry_point, Addr(0x8049000));
+
+        assert_eq!(file.program_headers.len(), 3);
+        let code = &file.program_headers[1];
+        assert_eq!(code.mem_range(), Addr(0x8049000)..Addr(0x804901f));
+        assert_eq!(code.flags, SegmentFlag::Read | SegmentFlag::Execute);
+
+        let data = file.section_by_name(".data").unwrap();
+        assert_eq!(data.data, b"hi there\n");
+        let (sym, offset) = file.symbol_for_addr(Addr(0x8049005)).unwrap();
+        assert_eq!((sym.name.as_str(), offset), ("_start", 5));
+    }
+
+    /// Lays out a file with a single `Load` segment, the way a big-endian
+    /// toolchain would
+    pub(crate) fn big_endian_file(class: super::Class, machine: super::Machine) -> Vec<u8> {
+        use super::Class;
+        let (word_size, ehsize, phentsize) = match class {
+            Class::Elf32 => (4, 52_u16, 32_u16),
+            Class::Elf64 => (8, 64, 56),
+        };
+        let word = |out: &mut Vec<u8>, x: u64| {
+            out.extend_from_slice(&x.to_be_bytes()[8 - word_size..]);
+        };
+        let code_offset = (ehsize + phentsize) as u64;
+        let code = [0xde, 0xad, 0xbe, 0xef];
+
+        let mut out = vec![0x7f, b'E', b'L', b'F', class as u8, 0x2, 0x1, 0x0];
+        out.extend_from_slice(&[0; 8]);
+        out.extend_from_slice(&0x2_u16.to_be_bytes()); // Exec
+        out.extend_from_slice(&u16::from(machine).to_be_bytes());
+        out.extend_from_slice(&1_u32.to_be_bytes());
+        word(&mut out, 0x10000 + code_offset); // entry point
+        word(&mut out, ehsize as u64); // ph_offset
+        word(&mut out, 0); // sh_offset
+        out.extend_from_slice(&0_u32.to_be_bytes());
+        for x in [ehsize, phentsize, 1, 0, 0, 0] {
+            out.extend_from_slice(&x.to_be_bytes());
+        }
+
+        out.extend_from_slice(&1_u32.to_be_bytes()); // Load
+        if class == Class::Elf64 {
+            out.extend_from_slice(&5_u32.to_be_bytes()); // R.X
+        }
+        word(&mut out, code_offset);
+        word(&mut out, 0x10000 + code_offset);
+        word(&mut out, 0x10000 + code_offset);
+        word(&mut out, code.len() as u64);
+        word(&mut out, code.len() as u64);
+        if class == Class::Elf32 {
+            out.extend_from_slice(&5_u32.to_be_bytes());
+        }
+        word(&mut out, 0x10000);
+
+        out.extend_from_slice(&code);
+        out
+    }
+
+    #[test]
+    fn big_endian() {
+        use super::{Addr, Class, Endianness, File, Machine, SegmentFlag, SegmentType};
+        for (class, machine) in [
+            (Class::Elf32, Machine::Mips),
+            (Class::Elf64, Machine::PowerPC64),
+        ] {
+            let input = big_endian_file(class, machine);
+            let (_, file) = File::parse(&input[..]).unwrap();
+
+            assert_eq!(file.class, class);
+            assert_eq!(file.endianness, Endianness::Big);
+            assert_eq!(file.machine, machine);
+
+            let ph = &file.program_headers[0];
+            assert_eq!(ph.r#type, SegmentType::Load);
+            assert_eq!(ph.flags, SegmentFlag::Read | SegmentFlag::Execute);
+            assert_eq!(ph.vaddr, file.entry_point);
+            assert_eq!(ph.memsz, Addr(4));
+            assert_eq!(ph.align, Addr(0x10000));
+            assert_eq!(ph.data, [0xde, 0xad, 0xbe, 0xef]);
+        }
+    }
 }
@@ -0,0 +1,310 @@
+use crate::{core_dump, parse, Addr, AuxvEntry, Class, Machine, MappedFile, PrPsInfo, PrStatus};
+use derive_try_from_primitive::TryFromPrimitive;
+use enumflags2::{bitflags, BitFlags};
+use serde::{Serialize, Serializer};
+use std::{fmt, path::PathBuf};
+
+/// Types of the notes owned by "GNU"; other owners use the same numbers for
+/// different things.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+#[repr(u32)]
+pub enum GnuNoteType {
+    AbiTag = 1,
+    HwCap = 2,
+    BuildId = 3,
+    GoldVersion = 4,
+    PropertyType0 = 5,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, Serialize)]
+#[repr(u32)]
+pub enum AbiTagOs {
+    Linux = 0,
+    Hurd = 1,
+    Solaris = 2,
+    FreeBsd = 3,
+}
+
+/// Control-flow enforcement (CET) features
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[bitflags]
+#[repr(u32)]
+pub enum X86Feature {
+    /// Indirect branch tracking
+    Ibt = 0x1,
+    /// Shadow stack
+    Shstk = 0x2,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum GnuProperty {
+    /// Features supported by every object linked in
+    X86Feature1And(#[serde(serialize_with = "crate::ser::flags")] BitFlags<X86Feature>),
+    Other {
+        r#type: u32,
+        #[serde(serialize_with = "crate::ser::hex")]
+        data: Vec<u8>,
+    },
+}
+
+impl GnuProperty {
+    const X86_FEATURE_1_AND: u32 = 0xc0000002;
+
+    pub fn parse(ctx: parse::Context, i: parse::Input) -> parse::Result<Self> {
+        use nom::{bytes::complete::take, sequence::tuple};
+
+        // properties are aligned to the word size, unlike notes
+        let align = match ctx.class {
+            Class::Elf32 => 4,
+            Class::Elf64 => 8,
+        };
+        let start = i;
+        let (i, (r#type, size)) = tuple((ctx.u32(), ctx.u32()))(i)?;
+        let (i, data) = take(size)(i)?;
+        let i = skip_padding(start, i, align);
+
+        let res = match (r#type, ctx.u32()(data)) {
+            (Self::X86_FEATURE_1_AND, Ok((&[], bits))) => {
+                Self::X86Feature1And(BitFlags::from_bits_truncate(bits))
+            }
+            _ => Self::Other {
+                r#type,
+                data: data.to_vec(),
+            },
+        };
+        Ok((i, res))
+    }
+}
+
+/// Identifies a build of a binary, to find its separate debug symbols
+#[derive(Clone, PartialEq, Eq)]
+pub struct BuildId(pub Vec<u8>);
+
+impl BuildId {
+    /// Where the debug symbols are, relative to a debug directory such as
+    /// `/usr/lib/debug`
+    pub fn debug_file_path(&self) -> PathBuf {
+        let hex = self.to_string();
+        let (dir, file) = hex.split_at(hex.len().min(2));
+        [".build-id", dir, &format!("{}.debug", file)]
+            .iter()
+            .collect()
+    }
+}
+
+impl fmt::Display for BuildId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for x in &self.0 {
+            write!(f, "{:02x}", x)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BuildId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl Serialize for BuildId {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum Note {
+    GnuBuildId(BuildId),
+    /// The oldest kernel version the binary runs on
+    GnuAbiTag {
+        os: AbiTagOs,
+        version: (u32, u32, u32),
+    },
+    GnuProperties(Vec<GnuProperty>),
+    /// Status of one thread of a core dump
+    CorePrStatus(PrStatus),
+    CorePrPsInfo(PrPsInfo),
+    CoreAuxv(Vec<AuxvEntry>),
+    CoreFile(Vec<MappedFile>),
+    /// Any note not decoded above, including ones that failed to decode
+    Other {
+        name: String,
+        r#type: u32,
+        #[serde(serialize_with = "crate::ser::hex")]
+        desc: Vec<u8>,
+    },
+}
+
+impl Note {
+    /// Parses a single note, whose name and descriptor start at multiples
+    /// of `align` bytes. Some notes have a layout that depends on `machine`.
+    pub fn parse(
+        ctx: parse::Context,
+        machine: Machine,
+        align: usize,
+        i: parse::Input,
+    ) -> parse::Result<Self> {
+        use nom::{bytes::complete::take, sequence::tuple};
+
+        let start = i;
+        let (i, (name_size, desc_size, r#type)) = tuple((ctx.u32(), ctx.u32(), ctx.u32()))(i)?;
+        let (i, name) = take(name_size)(i)?;
+        let i = skip_padding(start, i, align);
+        let (i, desc) = take(desc_size)(i)?;
+        let i = skip_padding(start, i, align);
+
+        let name = String::from_utf8_lossy(name.strip_suffix(&[0]).unwrap_or(name));
+        let decoded = match name.as_ref() {
+            "GNU" => Self::decode_gnu(ctx, r#type, desc),
+            "CORE" => core_dump::decode_note(ctx, machine, r#type, desc),
+            _ => None,
+        };
+        let res = decoded.unwrap_or_else(|| Self::Other {
+            name: name.into_owned(),
+            r#type,
+            desc: desc.to_vec(),
+        });
+        Ok((i, res))
+    }
+
+    fn decode_gnu(ctx: parse::Context, r#type: u32, desc: parse::Input) -> Option<Self> {
+        use nom::{combinator::all_consuming, multi::many0, sequence::tuple};
+
+        let res = match GnuNoteType::try_from(r#type).ok()? {
+            GnuNoteType::BuildId => Self::GnuBuildId(BuildId(desc.to_vec())),
+            GnuNoteType::AbiTag => {
+                let (_, (os, major, minor, patch)) =
+                    tuple((ctx.u32(), ctx.u32(), ctx.u32(), ctx.u32()))(desc).ok()?;
+                Self::GnuAbiTag {
+                    os: AbiTagOs::try_from(os).ok()?,
+                    version: (major, minor, patch),
+                }
+            }
+            GnuNoteType::PropertyType0 => {
+                let property = |i| GnuProperty::parse(ctx, i);
+                let (_, properties) = all_consuming(many0(property))(desc).ok()?;
+                Self::GnuProperties(properties)
+            }
+            _ => return None,
+        };
+        Some(res)
+    }
+
+    /// Parses the notes of a `PT_NOTE` segment or `SHT_NOTE` section, with
+    /// the alignment of that segment or section
+    pub fn parse_all(
+        ctx: parse::Context,
+        machine: Machine,
+        align: Addr,
+        i: parse::Input,
+    ) -> parse::Result<Vec<Self>> {
+        use nom::error::context;
+
+        // notes are 4-byte aligned, except for GNU property notes in ELF64
+        // files
+        let align = if align == Addr(8) { 8 } else { 4 };
+        let mut notes = Vec::new();
+        let mut i = i;
+        while !i.is_empty() {
+            let (rest, note) = context("Note", |i| Self::parse(ctx, machine, align, i))(i)?;
+            notes.push(note);
+            i = rest;
+        }
+        Ok((i, notes))
+    }
+}
+
+/// Skips to the next multiple of `align` bytes from `start`. The last entry
+/// of a segment may leave out its padding.
+fn skip_padding<'a>(
+    start: parse::Input<'a>,
+    i: parse::Input<'a>,
+    align: usize,
+) -> parse::Input<'a> {
+    let consumed = start.len() - i.len();
+    let padding = (align - consumed % align) % align;
+    &i[padding.min(i.len())..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AbiTagOs, BuildId, GnuProperty, Note, X86Feature};
+    use crate::{parse, Addr, Class, Endianness, File, Machine, SegmentContents, SegmentType};
+
+    #[test]
+    fn sample_notes() {
+        let input = include_bytes!("../../elk/samples/entrypoint");
+        let file = File::from_bytes(&input[..]).unwrap();
+        assert_eq!(
+            file.notes(),
+            [Note::GnuAbiTag {
+                os: AbiTagOs::Linux,
+                version: (2, 6, 32)
+            }]
+        );
+        assert_eq!(file.build_id(), None);
+
+        // x86 ISA needed: i486, from segments and from sections
+        let expected = [Note::GnuProperties(vec![GnuProperty::Other {
+            r#type: 0xc0000001,
+            data: vec![0x01, 0, 0, 0],
+        }])];
+        let input = include_bytes!("../../elk/samples/nodata");
+        let mut file = File::from_bytes(&input[..]).unwrap();
+        assert_eq!(file.notes(), expected);
+        file.program_headers.clear();
+        assert_eq!(file.notes(), expected);
+    }
+
+    #[test]
+    fn malformed_note_segment() {
+        let mut input = include_bytes!("../../elk/samples/entrypoint").to_vec();
+        // n_namesz of the ABI tag note, far past the end of the segment
+        input[0x2f8..0x2fc].copy_from_slice(&0xffff_u32.to_le_bytes());
+        let file = File::from_bytes(&input).unwrap();
+        assert!(matches!(
+            file.segment_of_type(SegmentType::Note).unwrap().contents,
+            SegmentContents::Unknown
+        ));
+        assert_eq!(file.notes(), []);
+        assert!(file.dynamic_table().is_some());
+    }
+
+    #[test]
+    fn build_id_and_cet() {
+        let ctx = parse::Context {
+            class: Class::Elf64,
+            endianness: Endianness::Little,
+        };
+        let mut input = Vec::new();
+        for x in [4_u32, 4, 3] {
+            input.extend_from_slice(&x.to_le_bytes());
+        }
+        input.extend_from_slice(b"GNU\0\xde\xad\xbe\xef\0\0\0\0");
+        for x in [4_u32, 16, 5] {
+            input.extend_from_slice(&x.to_le_bytes());
+        }
+        input.extend_from_slice(b"GNU\0");
+        for x in [0xc0000002_u32, 4, 3, 0] {
+            input.extend_from_slice(&x.to_le_bytes());
+        }
+
+        let (_, notes) = Note::parse_all(ctx, Machine::X86_64, Addr(8), &input).unwrap();
+        let build_id = BuildId(vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(
+            notes,
+            [
+                Note::GnuBuildId(build_id.clone()),
+                Note::GnuProperties(vec![GnuProperty::X86Feature1And(
+                    X86Feature::Ibt | X86Feature::Shstk
+                )])
+            ]
+        );
+        assert_eq!(build_id.to_string(), "deadbeef");
+        assert_eq!(
+            build_id.debug_file_path().to_str(),
+            Some(".build-id/de/adbeef.debug")
+        );
+    }
+}
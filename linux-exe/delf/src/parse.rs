@@ -1,20 +1,184 @@
+use crate::{Addr, Class, Endianness};
+use std::ops::Range;
+
 pub type Input<'a> = &'a [u8];
-pub type Result<'a, O> = nom::IResult<Input<'a>, O, nom::error::VerboseError<Input<'a>>>;
+pub type Result<'a, O> = nom::IResult<Input<'a>, O, Error<'a>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    Nom(nom::error::ErrorKind),
+    Context(&'static str),
+    /// A number that does not map to any variant of an enum, or to any
+    /// combination of flags
+    UnknownValue(u64),
+    /// The input ends before the header (or table of headers) starting at
+    /// this offset is complete
+    Truncated(Addr),
+    SegmentOutOfBounds {
+        index: usize,
+        range: Range<Addr>,
+    },
+    SectionOutOfBounds {
+        index: usize,
+        range: Range<Addr>,
+    },
+}
+
+/// Like nom's `VerboseError`, but able to carry what went wrong beyond
+/// which parser failed. The innermost error comes first.
+#[derive(Debug)]
+pub struct Error<'a> {
+    pub errors: Vec<(Input<'a>, ErrorKind)>,
+}
+
+impl<'a> Error<'a> {
+    /// An error that stops parsing altogether, rather than letting
+    /// combinators such as `alt` try something else
+    pub fn failure(input: Input<'a>, kind: ErrorKind) -> nom::Err<Self> {
+        nom::Err::Failure(Self {
+            errors: vec![(input, kind)],
+        })
+    }
+}
+
+impl<'a> nom::error::ParseError<Input<'a>> for Error<'a> {
+    fn from_error_kind(input: Input<'a>, kind: nom::error::ErrorKind) -> Self {
+        Self {
+            errors: vec![(input, ErrorKind::Nom(kind))],
+        }
+    }
+
+    fn append(input: Input<'a>, kind: nom::error::ErrorKind, mut other: Self) -> Self {
+        other.errors.push((input, ErrorKind::Nom(kind)));
+        other
+    }
+}
+
+impl<'a> nom::error::ContextError<Input<'a>> for Error<'a> {
+    fn add_context(input: Input<'a>, ctx: &'static str, mut other: Self) -> Self {
+        other.errors.push((input, ErrorKind::Context(ctx)));
+        other
+    }
+}
+
+impl<'a> nom::error::FromExternalError<Input<'a>, ErrorKind> for Error<'a> {
+    fn from_external_error(input: Input<'a>, _kind: nom::error::ErrorKind, e: ErrorKind) -> Self {
+        Self {
+            errors: vec![(input, e)],
+        }
+    }
+}
+
+/// Returns `len` bytes of `input` starting at `offset`, if they are all
+/// there
+pub fn slice(input: Input, offset: Addr, len: Addr) -> Option<Input> {
+    let start = usize::try_from(offset.0).ok()?;
+    let len = usize::try_from(len.0).ok()?;
+    input.get(start..start.checked_add(len)?)
+}
+
+/// How numbers are laid out in the file being parsed, as announced by the
+/// identification bytes at the start of the ELF header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Context {
+    pub class: Class,
+    pub endianness: Endianness,
+}
+
+impl Context {
+    fn nom_endianness(&self) -> nom::number::Endianness {
+        match self.endianness {
+            Endianness::Little => nom::number::Endianness::Little,
+            Endianness::Big => nom::number::Endianness::Big,
+        }
+    }
+
+    pub fn u16<'a>(self) -> impl Fn(Input<'a>) -> Result<'a, u16> {
+        move |i| nom::number::complete::u16(self.nom_endianness())(i)
+    }
+
+    pub fn u32<'a>(self) -> impl Fn(Input<'a>) -> Result<'a, u32> {
+        move |i| nom::number::complete::u32(self.nom_endianness())(i)
+    }
+
+    pub fn u64<'a>(self) -> impl Fn(Input<'a>) -> Result<'a, u64> {
+        move |i| nom::number::complete::u64(self.nom_endianness())(i)
+    }
+
+    /// Class-sized word (4 bytes for ELF32, 8 bytes for ELF64), widened to
+    /// 64 bits
+    pub fn word<'a>(self) -> impl Fn(Input<'a>) -> Result<'a, u64> {
+        use nom::combinator::map;
+        move |i| match self.class {
+            Class::Elf32 => map(self.u32(), u64::from)(i),
+            Class::Elf64 => self.u64()(i),
+        }
+    }
+
+    /// Signed class-sized word, sign-extended to 64 bits and stored as two's
+    /// complement
+    pub fn sword<'a>(self) -> impl Fn(Input<'a>) -> Result<'a, u64> {
+        use nom::combinator::map;
+        move |i| match self.class {
+            Class::Elf32 => map(self.u32(), |x| x as i32 as i64 as u64)(i),
+            Class::Elf64 => self.u64()(i),
+        }
+    }
+}
+
+/// Declares an enum of known values that keeps any other value in an
+/// `Other` variant, for fields where OSes and processors define their own,
+/// along with conversions from and to the number. `impl_parse_for_enum!`
+/// then never fails.
+#[macro_export]
+macro_rules! open_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $type: ident: $repr: ty {
+            $($(#[$variant_meta:meta])* $variant: ident = $value: literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        pub enum $type {
+            $($(#[$variant_meta])* $variant,)*
+            /// Any value not listed above
+            Other($repr),
+        }
+
+        impl From<$repr> for $type {
+            fn from(x: $repr) -> Self {
+                match x {
+                    $($value => Self::$variant,)*
+                    x => Self::Other(x),
+                }
+            }
+        }
+
+        impl From<$type> for $repr {
+            fn from(x: $type) -> Self {
+                match x {
+                    $($type::$variant => $value,)*
+                    $type::Other(x) => x,
+                }
+            }
+        }
+    };
+}
 
 #[macro_export]
 macro_rules! impl_parse_for_enum {
     ($type: ident, $number_parser: ident) => {
         impl $type {
-            pub fn parse(i: parse::Input) -> parse::Result<Self> {
-                use nom::{
-                    combinator::map_res,
-                    error::{context, ErrorKind},
-                    number::complete::$number_parser,
-                };
-                let parser = map_res($number_parser, |x| {
-                    Self::try_from(x).map_err(|_| ErrorKind::Alt)
-                });
-                context(stringify!($type), parser)(i)
+            pub fn parse<'a>(
+                ctx: parse::Context,
+            ) -> impl Fn(parse::Input<'a>) -> parse::Result<'a, Self> {
+                use nom::{combinator::map_res, error::context};
+                move |i| {
+                    let parser = map_res(ctx.$number_parser(), |x| {
+                        Self::try_from(x).map_err(|_| parse::ErrorKind::UnknownValue(x as u64))
+                    });
+                    context(stringify!($type), parser)(i)
+                }
             }
         }
     };
@@ -24,16 +188,17 @@ macro_rules! impl_parse_for_enum {
 macro_rules! impl_parse_for_enumflags {
     ($type: ident, $number_parser: ident) => {
         impl $type {
-            pub fn parse(i: parse::Input) -> parse::Result<enumflags2::BitFlags<Self>> {
-                use nom::{
-                    combinator::map_res,
-                    error::{context, ErrorKind},
-                    number::complete::$number_parser,
-                };
-                let parser = map_res($number_parser, |x| {
-                    enumflags2::BitFlags::<Self>::from_bits(x).map_err(|_| ErrorKind::Alt)
-                });
-                context(stringify!($type), parser)(i)
+            pub fn parse<'a>(
+                ctx: parse::Context,
+            ) -> impl Fn(parse::Input<'a>) -> parse::Result<'a, enumflags2::BitFlags<Self>> {
+                use nom::{combinator::map_res, error::context};
+                move |i| {
+                    let parser = map_res(ctx.$number_parser(), |x| {
+                        enumflags2::BitFlags::<Self>::from_bits(x)
+                            .map_err(|_| parse::ErrorKind::UnknownValue(x as u64))
+                    });
+                    context(stringify!($type), parser)(i)
+                }
             }
         }
     };
@@ -0,0 +1,126 @@
+//! Property tests feeding the parser mutated versions of the sample
+//! binaries: whatever the bytes, parsing and everything done with the
+//! result must return errors rather than panic.
+
+use crate::{exercise, Addr, FileRef};
+use proptest::{prelude::*, sample::Index};
+
+const SAMPLES: &[&[u8]] = &[
+    include_bytes!("../../elk/samples/hello"),
+    include_bytes!("../../elk/samples/hello.o"),
+    include_bytes!("../../elk/samples/hello32"),
+    include_bytes!("../../elk/samples/entrypoint"),
+    include_bytes!("../../elk/samples/greet"),
+    include_bytes!("../../elk/samples/libgreet.so"),
+    include_bytes!("../../elk/samples/crash.core"),
+    include_bytes!("../../elk/samples/lines"),
+    include_bytes!("../../elk/samples/tls"),
+    include_bytes!("../../elk/samples/libtls.so"),
+    include_bytes!("../../elk/samples/arm.o"),
+    include_bytes!("../../elk/samples/mips.o"),
+];
+
+/// Where in a sample to mutate: mostly the ELF header and the program and
+/// section header tables, which every other offset and size comes from
+#[derive(Debug, Clone)]
+enum Region {
+    Header,
+    ProgramHeaders,
+    SectionHeaders,
+    Anywhere,
+}
+
+#[derive(Debug, Clone)]
+struct Mutation {
+    region: Region,
+    at: Index,
+    width: usize,
+    value: u64,
+}
+
+impl Mutation {
+    fn apply(&self, file: &FileRef, input: &mut [u8]) {
+        let table = |offset: Addr, entsize: u16, count: u16| {
+            let start = usize::from(offset).min(input.len());
+            start..(start + entsize as usize * count as usize).min(input.len())
+        };
+        let range = match self.region {
+            Region::Header => 0..input.len().min(64),
+            Region::ProgramHeaders => table(file.ph_offset, file.ph_entsize, file.ph_count),
+            Region::SectionHeaders => table(file.sh_offset, file.sh_entsize, file.sh_count),
+            Region::Anywhere => 0..input.len(),
+        };
+        if range.is_empty() {
+            return;
+        }
+        let start = range.start + self.at.index(range.len());
+        let end = (start + self.width).min(range.end);
+        let bytes = self.value.to_le_bytes();
+        input[start..end].copy_from_slice(&bytes[..end - start]);
+    }
+}
+
+fn mutation() -> impl Strategy<Value = Mutation> {
+    let region = prop_oneof![
+        Just(Region::Header),
+        Just(Region::ProgramHeaders),
+        Just(Region::SectionHeaders),
+        Just(Region::Anywhere),
+    ];
+    // offsets and sizes at the edges break the most
+    let value = prop_oneof![
+        Just(0),
+        Just(1),
+        Just(u64::MAX),
+        Just(u32::MAX as u64),
+        Just(i64::MAX as u64),
+        0..0x1000_u64,
+        any::<u64>(),
+    ];
+    (
+        region,
+        any::<Index>(),
+        prop_oneof![Just(1), Just(2), Just(4), Just(8)],
+        value,
+    )
+        .prop_map(|(region, at, width, value)| Mutation {
+            region,
+            at,
+            width,
+            value,
+        })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn mutated_samples(
+        sample in any::<Index>(),
+        mutations in prop::collection::vec(mutation(), 1..8),
+        truncate in prop::option::weighted(0.1, any::<Index>()),
+    ) {
+        let original = SAMPLES[sample.index(SAMPLES.len())];
+        let file = FileRef::from_bytes(original).unwrap();
+        let mut input = original.to_vec();
+        for mutation in &mutations {
+            mutation.apply(&file, &mut input);
+        }
+        if let Some(len) = truncate {
+            input.truncate(len.index(input.len()));
+        }
+        exercise(&input);
+    }
+
+    #[test]
+    fn random_bytes(input in prop::collection::vec(any::<u8>(), 0..512)) {
+        exercise(&input);
+    }
+}
+
+#[test]
+fn samples() {
+    for input in SAMPLES {
+        exercise(input);
+    }
+}
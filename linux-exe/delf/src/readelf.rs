@@ -0,0 +1,835 @@
+use crate::{
+    Class, DynamicEntry, DynamicTag, File, Machine, ProgramHeader, SectionFlag, SectionHeader,
+    SectionType, SegmentContents, SegmentFlag, SegmentType, SymBind, SymType, SymVisibility, Type,
+    DF_1_PIE,
+};
+use enumflags2::BitFlags;
+use std::fmt;
+
+/// Section headers, program headers, the dynamic table and symbol tables,
+/// laid out like `readelf -lSdsW` does. Symbol versions are not shown.
+pub struct ReadElf<'a>(pub &'a File);
+
+impl File {
+    pub fn readelf(&self) -> ReadElf<'_> {
+        ReadElf(self)
+    }
+}
+
+impl fmt::Display for ReadElf<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.section_headers(f)?;
+        self.program_headers(f)?;
+        self.dynamic_table(f)?;
+        self.symbol_tables(f)
+    }
+}
+
+const ELFOSABI_GNU: u8 = 3;
+const ELFOSABI_FREEBSD: u8 = 9;
+
+const DF_NAMES: &[(u64, &str)] = &[
+    (0x1, "ORIGIN"),
+    (0x2, "SYMBOLIC"),
+    (0x4, "TEXTREL"),
+    (0x8, "BIND_NOW"),
+    (0x10, "STATIC_TLS"),
+];
+
+const DF_1_NAMES: &[(u64, &str)] = &[
+    (0x1, "NOW"),
+    (0x2, "GLOBAL"),
+    (0x4, "GROUP"),
+    (0x8, "NODELETE"),
+    (0x10, "LOADFLTR"),
+    (0x20, "INITFIRST"),
+    (0x40, "NOOPEN"),
+    (0x80, "ORIGIN"),
+    (0x100, "DIRECT"),
+    (0x200, "TRANS"),
+    (0x400, "INTERPOSE"),
+    (0x800, "NODEFLIB"),
+    (0x1000, "NODUMP"),
+    (0x2000, "CONFALT"),
+    (0x4000, "ENDFILTEE"),
+    (0x8000, "DISPRELDNE"),
+    (0x10000, "DISPRELPND"),
+    (0x20000, "NODIRECT"),
+    (0x40000, "IGNMULDEF"),
+    (0x80000, "NOKSYMS"),
+    (0x100000, "NOHDR"),
+    (0x200000, "EDITED"),
+    (0x400000, "NORELOC"),
+    (0x800000, "SYMINTPOSE"),
+    (0x1000000, "GLOBAUDIT"),
+    (0x2000000, "SINGLETON"),
+    (0x4000000, "STUB"),
+    (DF_1_PIE, "PIE"),
+];
+
+impl ReadElf<'_> {
+    /// Hex digits in an address
+    fn addr_width(&self) -> usize {
+        match self.0.class {
+            Class::Elf32 => 8,
+            Class::Elf64 => 16,
+        }
+    }
+
+    fn section_headers(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let file = self.0;
+        if file.section_headers.is_empty() {
+            return writeln!(f, "\nThere are no sections in this file.");
+        }
+        writeln!(
+            f,
+            "There are {} section headers, starting at offset {:#x}:",
+            file.section_headers.len(),
+            file.sh_offset.0
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Section Headers:")?;
+        let w = self.addr_width();
+        writeln!(
+            f,
+            "  [Nr] Name              Type            {:<w$} Off    Size   ES Flg Lk Inf Al",
+            if file.class == Class::Elf64 {
+                "Address"
+            } else {
+                "Addr"
+            },
+        )?;
+        for (i, sh) in file.section_headers.iter().enumerate() {
+            let flags = section_flag_keys(file, sh);
+            writeln!(
+                f,
+                "  [{:2}] {:<17} {:<15} {:0w$x} {:06x} {:06x} {:02x} {:>3} {:2} {:3} {:2}",
+                i,
+                sh.name,
+                section_type_name(file, sh.r#type),
+                sh.addr.0,
+                sh.offset.0,
+                sh.size.0,
+                sh.entsize.0,
+                flags,
+                sh.link,
+                sh.info,
+                sh.addralign.0,
+            )?;
+        }
+        writeln!(f, "Key to Flags:")?;
+        writeln!(
+            f,
+            "  W (write), A (alloc), X (execute), M (merge), S (strings), I (info),"
+        )?;
+        writeln!(
+            f,
+            "  L (link order), O (extra OS processing required), G (group), T (TLS),"
+        )?;
+        writeln!(
+            f,
+            "  C (compressed), x (unknown), o (OS specific), E (exclude),"
+        )?;
+        write!(f, "  ")?;
+        if has_gnu_flags(file) {
+            write!(f, "R (retain), ")?;
+        }
+        write!(f, "D (mbind), ")?;
+        match file.machine {
+            Machine::X86_64 => write!(f, "l (large), ")?,
+            Machine::Arm => write!(f, "y (purecode), ")?,
+            _ => {}
+        }
+        writeln!(f, "p (processor specific)")
+    }
+
+    fn program_headers(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let file = self.0;
+        if file.program_headers.is_empty() {
+            return writeln!(f, "\nThere are no program headers in this file.");
+        }
+        writeln!(f)?;
+        writeln!(f, "Elf file type is {}", file_type_name(file))?;
+        writeln!(f, "Entry point {:#x}", file.entry_point.0)?;
+        writeln!(
+            f,
+            "There are {} program headers, starting at offset {}",
+            file.program_headers.len(),
+            file.ph_offset.0
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Program Headers:")?;
+        let (w, sw) = match file.class {
+            Class::Elf32 => {
+                writeln!(
+                    f,
+                    "  Type           Offset   VirtAddr   PhysAddr   FileSiz MemSiz  Flg Align"
+                )?;
+                (8, 5)
+            }
+            Class::Elf64 => {
+                writeln!(f, "  Type           Offset   VirtAddr           PhysAddr           FileSiz  MemSiz   Flg Align")?;
+                (16, 6)
+            }
+        };
+        for ph in &file.program_headers {
+            let flag = |flag, c| if ph.flags.contains(flag) { c } else { ' ' };
+            writeln!(
+                f,
+                "  {:<14.14} 0x{:06x} 0x{:0w$x} 0x{:0w$x} 0x{:0sw$x} 0x{:0sw$x} {}{}{} {:#x}",
+                segment_type_name(file, ph.r#type),
+                ph.offset.0,
+                ph.vaddr.0,
+                ph.paddr.0,
+                ph.filesz.0,
+                ph.memsz.0,
+                flag(SegmentFlag::Read, 'R'),
+                flag(SegmentFlag::Write, 'W'),
+                flag(SegmentFlag::Execute, 'E'),
+                ph.align.0,
+            )?;
+            if ph.r#type == SegmentType::Interp {
+                let interp = ph.data.split(|&b| b == 0).next().unwrap_or_default();
+                writeln!(
+                    f,
+                    "      [Requesting program interpreter: {}]",
+                    String::from_utf8_lossy(interp)
+                )?;
+            }
+        }
+
+        if file.section_headers.is_empty() {
+            return Ok(());
+        }
+        writeln!(f)?;
+        writeln!(f, " Section to Segment mapping:")?;
+        writeln!(f, "  Segment Sections...")?;
+        for (i, ph) in file.program_headers.iter().enumerate() {
+            write!(f, "   {:02}     ", i)?;
+            for sh in file.section_headers.iter().skip(1) {
+                if section_in_segment(sh, ph) {
+                    write!(f, "{} ", sh.name)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+
+    fn dynamic_table(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let file = self.0;
+        let (ph, entries) = match file.segment_of_type(SegmentType::Dynamic) {
+            Some(
+                ph @ ProgramHeader {
+                    contents: SegmentContents::Dynamic(entries),
+                    ..
+                },
+            ) => (ph, entries),
+            _ => return writeln!(f, "\nThere is no dynamic section in this file."),
+        };
+        writeln!(f)?;
+        writeln!(
+            f,
+            "Dynamic section at offset {:#x} contains {} {}:",
+            ph.offset.0,
+            entries.len(),
+            if entries.len() == 1 {
+                "entry"
+            } else {
+                "entries"
+            }
+        )?;
+        writeln!(f, "  Tag        Type                         Name/Value")?;
+        let (w, type_width) = match file.class {
+            Class::Elf32 => (8, 27_usize),
+            Class::Elf64 => (16, 19),
+        };
+        for entry in entries {
+            let name = dynamic_tag_name(entry.tag);
+            writeln!(
+                f,
+                " 0x{:0w$x} ({}){:pad$}{}",
+                u64::from(entry.tag),
+                name,
+                "",
+                self.dynamic_value(entry),
+                // names too long for the column push the value further
+                // out, by as much as they overflow
+                pad = type_width.abs_diff(name.len()).max(1),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn dynamic_value(&self, entry: &DynamicEntry) -> String {
+        let string = |what| {
+            let s = self.0.dynamic_string(entry.addr).unwrap_or_default();
+            format!("{}: [{}]", what, s)
+        };
+        let value = entry.addr.0;
+        match entry.tag {
+            DynamicTag::Needed => string("Shared library"),
+            DynamicTag::SoName => string("Library soname"),
+            DynamicTag::RPath => string("Library rpath"),
+            DynamicTag::RunPath => string("Library runpath"),
+            DynamicTag::Auxiliary => string("Auxiliary library"),
+            DynamicTag::Filter => string("Filter library"),
+            DynamicTag::Audit => string("Audit library"),
+            DynamicTag::DepAudit => string("Dependency audit library"),
+            DynamicTag::BindNow | DynamicTag::TextRel => String::new(),
+            DynamicTag::Flags => {
+                let names = (0..64)
+                    .map(|bit| 1 << bit)
+                    .filter(|flag| value & flag != 0)
+                    .map(|flag| {
+                        DF_NAMES
+                            .iter()
+                            .find(|(f, _)| *f == flag)
+                            .map_or("unknown", |(_, name)| name)
+                    })
+                    .collect::<Vec<_>>();
+                names.join(" ")
+            }
+            DynamicTag::Flags1 => {
+                let mut res = "Flags:".to_string();
+                let mut rest = value;
+                for (flag, name) in DF_1_NAMES {
+                    if value & flag != 0 {
+                        res += " ";
+                        res += name;
+                        rest &= !flag;
+                    }
+                }
+                if rest != 0 {
+                    res += &format!(" {:x}", rest);
+                }
+                res
+            }
+            DynamicTag::PltRel => dynamic_tag_name(DynamicTag::from(value)),
+            DynamicTag::PltRelSz
+            | DynamicTag::RelaSz
+            | DynamicTag::RelaEnt
+            | DynamicTag::StrSz
+            | DynamicTag::SymEnt
+            | DynamicTag::RelSz
+            | DynamicTag::RelEnt
+            | DynamicTag::RelrSz
+            | DynamicTag::RelrEnt
+            | DynamicTag::InitArraySz
+            | DynamicTag::FiniArraySz
+            | DynamicTag::PreInitArraySz
+            | DynamicTag::SymInSz
+            | DynamicTag::SymInEnt
+            | DynamicTag::MoveEnt
+            | DynamicTag::MoveSz
+            | DynamicTag::GnuConflictSz
+            | DynamicTag::GnuLibListSz
+            | DynamicTag::PltPadSz => format!("{} (bytes)", value),
+            DynamicTag::RelaCount
+            | DynamicTag::RelCount
+            | DynamicTag::VerDefNum
+            | DynamicTag::VerNeedNum => value.to_string(),
+            _ => format!("{:#x}", value),
+        }
+    }
+
+    fn symbol_tables(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let file = self.0;
+        if file.section_headers.is_empty() {
+            writeln!(f)?;
+            return writeln!(
+                f,
+                "Dynamic symbol information is not available for displaying symbols."
+            );
+        }
+        let w = self.addr_width();
+        for sh in &file.section_headers {
+            if !matches!(sh.r#type, SectionType::SymTab | SectionType::DynSym) {
+                continue;
+            }
+            let syms = file.section_symbols(sh).collect::<Vec<_>>();
+            writeln!(f)?;
+            writeln!(
+                f,
+                "Symbol table '{}' contains {} {}:",
+                sh.name,
+                syms.len(),
+                if syms.len() == 1 { "entry" } else { "entries" }
+            )?;
+            match file.class {
+                Class::Elf32 => {
+                    writeln!(f, "   Num:    Value  Size Type    Bind   Vis      Ndx Name")?
+                }
+                Class::Elf64 => writeln!(
+                    f,
+                    "   Num:    Value          Size Type    Bind   Vis      Ndx Name"
+                )?,
+            }
+            for (i, sym) in syms.iter().enumerate() {
+                // section symbols are named after their section
+                let section = match sym.r#type {
+                    SymType::Section if sym.name.is_empty() => sym.shndx.get(),
+                    _ => None,
+                };
+                let name = match section.and_then(|i| file.section_headers.get(i)) {
+                    Some(sh) => &sh.name,
+                    None => &sym.name,
+                };
+                writeln!(
+                    f,
+                    "{:6}: {:0w$x} {:>5} {:<7} {:<6} {:<7} {:>4} {}",
+                    i,
+                    sym.value.0,
+                    // wider sizes would break the layout
+                    if sym.size > 99999 {
+                        format!("{:#x}", sym.size)
+                    } else {
+                        sym.size.to_string()
+                    },
+                    sym_type_name(self.0, sym.r#type),
+                    sym_bind_name(self.0, sym.bind),
+                    sym_visibility_name(sym.visibility),
+                    format!("{:?}", sym.shndx),
+                    name,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `SHF_GNU_RETAIN` and `STT_GNU_IFUNC` mean anything for the file's OS
+fn has_gnu_flags(file: &File) -> bool {
+    matches!(file.os_abi, ELFOSABI_GNU | ELFOSABI_FREEBSD)
+}
+
+fn file_type_name(file: &File) -> &'static str {
+    match file.r#type {
+        Type::None => "NONE (None)",
+        Type::Rel => "REL (Relocatable file)",
+        Type::Exec => "EXEC (Executable file)",
+        Type::Dyn if file.is_pie() => "DYN (Position-Independent Executable file)",
+        Type::Dyn => "DYN (Shared object file)",
+        Type::Core => "CORE (Core file)",
+    }
+}
+
+/// Whether `readelf` lists `sh` as part of `ph`, which takes both file
+/// offsets and addresses into account, with special cases for `.tbss` and
+/// empty sections
+fn section_in_segment(sh: &SectionHeader, ph: &ProgramHeader) -> bool {
+    let tls = sh.flags.contains(SectionFlag::Tls);
+    let nobits = sh.r#type == SectionType::NoBits;
+    let alloc = sh.flags.contains(SectionFlag::Alloc);
+    // `.tbss` takes no room outside of the TLS segment
+    if tls && nobits && ph.r#type != SegmentType::Tls {
+        return false;
+    }
+    let size = sh.size.0;
+
+    let type_ok = if tls {
+        matches!(
+            ph.r#type,
+            SegmentType::Tls | SegmentType::GnuRelRo | SegmentType::Load
+        )
+    } else {
+        !matches!(ph.r#type, SegmentType::Tls | SegmentType::Phdr)
+    };
+    // the `- 1`s wrap around for empty segments, on purpose
+    let offset_ok = nobits
+        || (sh.offset >= ph.offset
+            && (sh.offset - ph.offset).0 <= ph.filesz.0.wrapping_sub(1)
+            && (sh.offset - ph.offset)
+                .0
+                .checked_add(size)
+                .is_some_and(|end| end <= ph.filesz.0));
+    let addr_ok = !alloc
+        || (sh.addr >= ph.vaddr
+            && (sh.addr - ph.vaddr).0 <= ph.memsz.0.wrapping_sub(1)
+            && (sh.addr - ph.vaddr)
+                .0
+                .checked_add(size)
+                .is_some_and(|end| end <= ph.memsz.0));
+    // empty sections at the very start of a dynamic segment are not in it
+    let dynamic_ok = ph.r#type != SegmentType::Dynamic
+        || size != 0
+        || ph.memsz.0 == 0
+        || ((nobits || (sh.offset > ph.offset && (sh.offset - ph.offset).0 < ph.filesz.0))
+            && (!alloc || (sh.addr > ph.vaddr && (sh.addr - ph.vaddr).0 < ph.memsz.0)));
+    type_ok && offset_ok && addr_ok && dynamic_ok
+}
+
+/// One letter per flag, lowest bit first. OS- and processor-specific bits
+/// without a letter of their own share a single `o` or `p`.
+fn section_flag_keys(file: &File, sh: &SectionHeader) -> String {
+    const SHF_X86_64_LARGE: u64 = 0x1000_0000;
+    const SHF_ARM_PURECODE: u64 = 0x2000_0000;
+
+    let mut keys = String::new();
+    let mut bits = sh.flags.bits() | sh.other_flags;
+    while bits != 0 {
+        let bit = bits & bits.wrapping_neg();
+        bits &= !bit;
+        let flag = BitFlags::<SectionFlag>::from_bits(bit)
+            .ok()
+            .and_then(|flags| flags.iter().next())
+            // OS-specific, for other systems
+            .filter(|&flag| flag != SectionFlag::GnuRetain || has_gnu_flags(file));
+        let key = match flag {
+            Some(flag) => section_flag_key(flag),
+            None if bit == SHF_X86_64_LARGE && file.machine == Machine::X86_64 => 'l',
+            None if bit == SHF_ARM_PURECODE && file.machine == Machine::Arm => 'y',
+            None if bit & SectionFlag::MASK_OS != 0 => {
+                bits &= !SectionFlag::MASK_OS;
+                'o'
+            }
+            None if bit & SectionFlag::MASK_PROC != 0 => {
+                bits &= !SectionFlag::MASK_PROC;
+                'p'
+            }
+            None => 'x',
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+fn section_flag_key(flag: SectionFlag) -> char {
+    match flag {
+        SectionFlag::Write => 'W',
+        SectionFlag::Alloc => 'A',
+        SectionFlag::ExecInstr => 'X',
+        SectionFlag::Merge => 'M',
+        SectionFlag::Strings => 'S',
+        SectionFlag::InfoLink => 'I',
+        SectionFlag::LinkOrder => 'L',
+        SectionFlag::OsNonConforming => 'O',
+        SectionFlag::Group => 'G',
+        SectionFlag::Tls => 'T',
+        SectionFlag::Compressed => 'C',
+        SectionFlag::GnuRetain => 'R',
+        SectionFlag::Exclude => 'E',
+    }
+}
+
+fn section_type_name(file: &File, r#type: SectionType) -> String {
+    let name = match r#type {
+        SectionType::Null => "NULL",
+        SectionType::ProgBits => "PROGBITS",
+        SectionType::SymTab => "SYMTAB",
+        SectionType::StrTab => "STRTAB",
+        SectionType::Rela => "RELA",
+        SectionType::Hash => "HASH",
+        SectionType::Dynamic => "DYNAMIC",
+        SectionType::Note => "NOTE",
+        SectionType::NoBits => "NOBITS",
+        SectionType::Rel => "REL",
+        SectionType::ShLib => "SHLIB",
+        SectionType::DynSym => "DYNSYM",
+        SectionType::InitArray => "INIT_ARRAY",
+        SectionType::FiniArray => "FINI_ARRAY",
+        SectionType::PreInitArray => "PREINIT_ARRAY",
+        SectionType::Group => "GROUP",
+        SectionType::SymTabShndx => "SYMTAB SECTION INDICES",
+        SectionType::Relr => "RELR",
+        SectionType::GnuAttributes => "GNU_ATTRIBUTES",
+        SectionType::GnuHash => "GNU_HASH",
+        SectionType::GnuLibList => "GNU_LIBLIST",
+        SectionType::GnuVerDef => "VERDEF",
+        SectionType::GnuVerNeed => "VERNEED",
+        SectionType::GnuVerSym => "VERSYM",
+        SectionType::Other(x) => {
+            return match (file.machine, x) {
+                (Machine::X86_64, 0x7000_0001) => "X86_64_UNWIND".into(),
+                (Machine::Arm, 0x7000_0001) => "ARM_EXIDX".into(),
+                (Machine::Arm, 0x7000_0002) => "ARM_PREEMPTMAP".into(),
+                (Machine::Arm, 0x7000_0003) => "ARM_ATTRIBUTES".into(),
+                (Machine::AArch64, 0x7000_0003) => "AARCH64_ATTRIBUTES".into(),
+                (Machine::Mips, 0x7000_0006) => "MIPS_REGINFO".into(),
+                (Machine::Mips, 0x7000_000d) => "MIPS_OPTIONS".into(),
+                (Machine::Mips, 0x7000_001e) => "MIPS_DWARF".into(),
+                (Machine::Mips, 0x7000_002a) => "MIPS_ABIFLAGS".into(),
+                (Machine::RiscV, 0x7000_0003) => "RISCV_ATTRIBUTES".into(),
+                (_, 0x7000_0000..=0x7fff_ffff) => format!("LOPROC+{}", c_hex(x - 0x7000_0000)),
+                (_, 0x6000_0000..=0x6fff_ffff) => format!("LOOS+{}", c_hex(x - 0x6000_0000)),
+                (_, 0x8000_0000..) => format!("LOUSER+{}", c_hex(x - 0x8000_0000)),
+                _ => format!("{:08x}: <unknown>", x),
+            };
+        }
+    };
+    name.into()
+}
+
+fn segment_type_name(file: &File, r#type: SegmentType) -> String {
+    let name = match r#type {
+        SegmentType::Null => "NULL",
+        SegmentType::Load => "LOAD",
+        SegmentType::Dynamic => "DYNAMIC",
+        SegmentType::Interp => "INTERP",
+        SegmentType::Note => "NOTE",
+        SegmentType::ShLib => "SHLIB",
+        SegmentType::Phdr => "PHDR",
+        SegmentType::Tls => "TLS",
+        SegmentType::GnuEhFrame => "GNU_EH_FRAME",
+        SegmentType::GnuStack => "GNU_STACK",
+        SegmentType::GnuRelRo => "GNU_RELRO",
+        SegmentType::GnuProperty => "GNU_PROPERTY",
+        SegmentType::GnuSFrame => "GNU_SFRAME",
+        SegmentType::Other(x) => {
+            return match (file.machine, x) {
+                (Machine::Arm, 0x7000_0001) => "EXIDX".into(),
+                (Machine::AArch64, 0x7000_0002) => "AARCH64_MEMTAG_MTE".into(),
+                (Machine::Mips, 0x7000_0000) => "REGINFO".into(),
+                (Machine::Mips, 0x7000_0001) => "RTPROC".into(),
+                (Machine::Mips, 0x7000_0002) => "OPTIONS".into(),
+                (Machine::Mips, 0x7000_0003) => "ABIFLAGS".into(),
+                (Machine::RiscV, 0x7000_0003) => "RISCV_ATTRIBUTES".into(),
+                (_, 0x7000_0000..=0x7fff_ffff) => format!("LOPROC+{}", c_hex(x - 0x7000_0000)),
+                (_, 0x6000_0000..=0x6fff_ffff) => format!("LOOS+{}", c_hex(x - 0x6000_0000)),
+                _ => format!("<unknown>: {:x}", x),
+            };
+        }
+    };
+    name.into()
+}
+
+/// Like C's `%#x`, which leaves out the `0x` of zero
+fn c_hex(x: u32) -> String {
+    match x {
+        0 => "0".into(),
+        x => format!("{:#x}", x),
+    }
+}
+
+fn dynamic_tag_name(tag: DynamicTag) -> String {
+    let name = match tag {
+        DynamicTag::Null => "NULL",
+        DynamicTag::Needed => "NEEDED",
+        DynamicTag::PltRelSz => "PLTRELSZ",
+        DynamicTag::PltGot => "PLTGOT",
+        DynamicTag::Hash => "HASH",
+        DynamicTag::StrTab => "STRTAB",
+        DynamicTag::SymTab => "SYMTAB",
+        DynamicTag::Rela => "RELA",
+        DynamicTag::RelaSz => "RELASZ",
+        DynamicTag::RelaEnt => "RELAENT",
+        DynamicTag::StrSz => "STRSZ",
+        DynamicTag::SymEnt => "SYMENT",
+        DynamicTag::Init => "INIT",
+        DynamicTag::Fini => "FINI",
+        DynamicTag::SoName => "SONAME",
+        DynamicTag::RPath => "RPATH",
+        DynamicTag::Symbolic => "SYMBOLIC",
+        DynamicTag::Rel => "REL",
+        DynamicTag::RelSz => "RELSZ",
+        DynamicTag::RelEnt => "RELENT",
+        DynamicTag::PltRel => "PLTREL",
+        DynamicTag::Debug => "DEBUG",
+        DynamicTag::TextRel => "TEXTREL",
+        DynamicTag::JmpRel => "JMPREL",
+        DynamicTag::BindNow => "BIND_NOW",
+        DynamicTag::InitArray => "INIT_ARRAY",
+        DynamicTag::FiniArray => "FINI_ARRAY",
+        DynamicTag::InitArraySz => "INIT_ARRAYSZ",
+        DynamicTag::FiniArraySz => "FINI_ARRAYSZ",
+        DynamicTag::RunPath => "RUNPATH",
+        DynamicTag::Flags => "FLAGS",
+        DynamicTag::PreInitArray => "PREINIT_ARRAY",
+        DynamicTag::PreInitArraySz => "PREINIT_ARRAYSZ",
+        DynamicTag::SymTabShndx => "SYMTAB_SHNDX",
+        DynamicTag::RelrSz => "RELRSZ",
+        DynamicTag::Relr => "RELR",
+        DynamicTag::RelrEnt => "RELRENT",
+        DynamicTag::GnuPrelinked => "GNU_PRELINKED",
+        DynamicTag::GnuConflictSz => "GNU_CONFLICTSZ",
+        DynamicTag::GnuLibListSz => "GNU_LIBLISTSZ",
+        DynamicTag::Checksum => "CHECKSUM",
+        DynamicTag::PltPadSz => "PLTPADSZ",
+        DynamicTag::MoveEnt => "MOVEENT",
+        DynamicTag::MoveSz => "MOVESZ",
+        DynamicTag::PosFlag1 => "POSFLAG_1",
+        DynamicTag::SymInSz => "SYMINSZ",
+        DynamicTag::SymInEnt => "SYMINENT",
+        DynamicTag::GnuHash => "GNU_HASH",
+        DynamicTag::TlsDescPlt => "TLSDESC_PLT",
+        DynamicTag::TlsDescGot => "TLSDESC_GOT",
+        DynamicTag::GnuConflict => "GNU_CONFLICT",
+        DynamicTag::GnuLibList => "GNU_LIBLIST",
+        DynamicTag::Config => "CONFIG",
+        DynamicTag::DepAudit => "DEPAUDIT",
+        DynamicTag::Audit => "AUDIT",
+        DynamicTag::PltPad => "PLTPAD",
+        DynamicTag::MoveTab => "MOVETAB",
+        DynamicTag::SymInfo => "SYMINFO",
+        DynamicTag::VerSym => "VERSYM",
+        DynamicTag::RelaCount => "RELACOUNT",
+        DynamicTag::RelCount => "RELCOUNT",
+        DynamicTag::Flags1 => "FLAGS_1",
+        DynamicTag::VerDef => "VERDEF",
+        DynamicTag::VerDefNum => "VERDEFNUM",
+        DynamicTag::VerNeed => "VERNEED",
+        DynamicTag::VerNeedNum => "VERNEEDNUM",
+        DynamicTag::Auxiliary => "AUXILIARY",
+        DynamicTag::Filter => "FILTER",
+        DynamicTag::Other(x @ 0x7000_0000..=0x7fff_ffff) => {
+            return format!("Processor Specific: {:x}", x)
+        }
+        DynamicTag::Other(x @ 0x6000_000d..=0x6fff_f000) => {
+            return format!("Operating System specific: {:x}", x)
+        }
+        DynamicTag::Other(x) => return format!("<unknown>: {:x}", x),
+    };
+    name.into()
+}
+
+fn sym_type_name(file: &File, r#type: SymType) -> String {
+    let name = match r#type {
+        SymType::NoType => "NOTYPE",
+        SymType::Object => "OBJECT",
+        SymType::Func => "FUNC",
+        SymType::Section => "SECTION",
+        SymType::File => "FILE",
+        SymType::Common => "COMMON",
+        SymType::Tls => "TLS",
+        SymType::GnuIFunc if has_gnu_flags(file) => "IFUNC",
+        SymType::GnuIFunc => "<OS specific>: 10",
+        SymType::Other(x) => return reserved_sym_value_name(x),
+    };
+    name.into()
+}
+
+fn sym_bind_name(file: &File, bind: SymBind) -> String {
+    let name = match bind {
+        SymBind::Local => "LOCAL",
+        SymBind::Global => "GLOBAL",
+        SymBind::Weak => "WEAK",
+        SymBind::GnuUnique if file.os_abi == ELFOSABI_GNU => "UNIQUE",
+        SymBind::GnuUnique => "<OS specific>: 10",
+        SymBind::Other(x) => return reserved_sym_value_name(x),
+    };
+    name.into()
+}
+
+/// Symbol types and bindings share the same reserved ranges
+fn reserved_sym_value_name(x: u8) -> String {
+    match x {
+        13..=15 => format!("<processor specific>: {}", x),
+        10..=12 => format!("<OS specific>: {}", x),
+        _ => format!("<unknown>: {}", x),
+    }
+}
+
+fn sym_visibility_name(visibility: SymVisibility) -> &'static str {
+    match visibility {
+        SymVisibility::Default => "DEFAULT",
+        SymVisibility::Internal => "INTERNAL",
+        SymVisibility::Hidden => "HIDDEN",
+        SymVisibility::Protected => "PROTECTED",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::File;
+
+    #[test]
+    fn readelf_lines() {
+        let input = include_bytes!("../../elk/samples/libgreet.so");
+        let (_, file) = File::parse(&input[..]).unwrap();
+        let output = file.readelf().to_string();
+        for line in [
+            "  [ 5] .text             PROGBITS        0000000000001000 001000 00001f 00  AX  0   0  1",
+            "  LOAD           0x001000 0x0000000000001000 0x0000000000001000 0x00001f 0x00001f R E 0x1000",
+            "   03     .dynamic .data ",
+            " 0x000000000000000e (SONAME)             Library soname: [libgreet.so]",
+            " 0x000000000000000a (STRSZ)              41 (bytes)",
+            "     2: 0000000000001000     0 FUNC    GLOBAL DEFAULT    5 greet",
+        ] {
+            assert!(output.lines().any(|l| l == line), "missing {:?}", line);
+        }
+    }
+
+    #[test]
+    fn os_and_processor_flags() {
+        let mut input = include_bytes!("../../elk/samples/hello").to_vec();
+        let (_, file) = File::parse(&input[..]).unwrap();
+        // `.text`, with SHF_X86_64_LARGE and two OS-specific bits, one of
+        // them SHF_GNU_RETAIN, which means nothing for a System V file
+        let flags = usize::from(file.sh_offset) + 64 + 8;
+        input[flags..][..8].copy_from_slice(&0x1030_0006_u64.to_le_bytes());
+
+        let (_, file) = File::parse(&input[..]).unwrap();
+        let text = file.section_by_name(".text").unwrap();
+        assert_eq!(text.other_flags, 0x1010_0000);
+        let output = file.readelf().to_string();
+        assert!(output.contains(" 00 AXol  0 "), "{}", output);
+        assert_eq!(file.to_bytes(), input);
+    }
+
+    #[test]
+    fn processor_specific_types() {
+        use crate::{Machine, SectionType, SegmentType};
+
+        for (input, lines) in [
+            (
+                &include_bytes!("../../elk/samples/arm.o")[..],
+                [
+                    ".ARM.exidx        ARM_EXIDX ",
+                    ".ARM.attributes   ARM_ATTRIBUTES ",
+                ],
+            ),
+            (
+                &include_bytes!("../../elk/samples/mips.o")[..],
+                [
+                    ".reginfo          MIPS_REGINFO ",
+                    ".MIPS.abiflags    MIPS_ABIFLAGS ",
+                ],
+            ),
+            (
+                &include_bytes!("../../elk/samples/riscv.o")[..],
+                [
+                    ".riscv.attributes RISCV_ATTRIBUTES ",
+                    ".text             PROGBITS ",
+                ],
+            ),
+        ] {
+            let (_, file) = File::parse(input).unwrap();
+            let output = file.readelf().to_string();
+            for line in lines {
+                assert!(output.contains(line), "missing {:?}", line);
+            }
+        }
+
+        let mut input = include_bytes!("../../elk/samples/hello").to_vec();
+        let (_, file) = File::parse(&input[..]).unwrap();
+        let ph = usize::from(file.ph_offset) + 56;
+        input[ph..][..4].copy_from_slice(&0x7000_0001_u32.to_le_bytes());
+        input[18..][..2].copy_from_slice(&0x1234_u16.to_le_bytes());
+        let (_, file) = File::parse(&input[..]).unwrap();
+        assert_eq!(file.machine, Machine::Other(0x1234));
+        assert_eq!(
+            file.program_headers[1].r#type,
+            SegmentType::Other(0x7000_0001)
+        );
+        assert!(file
+            .readelf()
+            .to_string()
+            .contains("\n  LOPROC+0x1     0x001000 "));
+        assert_eq!(file.to_bytes(), input);
+
+        let (_, file) = File::parse(&include_bytes!("../../elk/samples/arm.o")[..]).unwrap();
+        let exidx = file.section_by_name(".ARM.exidx").unwrap();
+        assert_eq!(exidx.r#type, SectionType::Other(0x7000_0001));
+    }
+
+    #[test]
+    fn readelf_32_bit() {
+        let input = include_bytes!("../../elk/samples/hello32");
+        let (_, file) = File::parse(&input[..]).unwrap();
+        let output = file.readelf().to_string();
+        assert!(output.contains(
+            "  Type           Offset   VirtAddr   PhysAddr   FileSiz MemSiz  Flg Align\n"
+        ));
+        assert!(output.contains("\nThere is no dynamic section in this file.\n"));
+    }
+}
@@ -0,0 +1,30 @@
+//! `serde` helpers for fields whose derived serialization would not be of
+//! much use
+
+use enumflags2::{BitFlag, BitFlags};
+use serde::Serializer;
+use std::fmt;
+
+/// Flags as a list of their names
+pub(crate) fn flags<T, S>(flags: &BitFlags<T>, s: S) -> Result<S::Ok, S::Error>
+where
+    T: BitFlag + fmt::Debug,
+    S: Serializer,
+{
+    s.collect_seq(flags.iter().map(|flag| format!("{:?}", flag)))
+}
+
+/// Bytes as a hex string
+pub(crate) fn hex<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(
+        &bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>(),
+    )
+}
+
+/// Bytes as their count only
+pub(crate) fn len<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_u64(bytes.len() as u64)
+}
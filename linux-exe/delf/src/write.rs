@@ -0,0 +1,308 @@
+use crate::{parse, Addr, Class, Endianness, File, ProgramHeader, SectionHeader};
+
+/// Counterpart of `parse::Context`: lays numbers out with the class and
+/// byte order of the file being written
+struct Writer {
+    ctx: parse::Context,
+    out: Vec<u8>,
+}
+
+impl Writer {
+    fn new(ctx: parse::Context) -> Self {
+        Self {
+            ctx,
+            out: Vec::new(),
+        }
+    }
+
+    fn bytes(&mut self, x: &[u8]) {
+        self.out.extend_from_slice(x);
+    }
+
+    fn u8(&mut self, x: u8) {
+        self.out.push(x);
+    }
+
+    fn u16(&mut self, x: u16) {
+        match self.ctx.endianness {
+            Endianness::Little => self.bytes(&x.to_le_bytes()),
+            Endianness::Big => self.bytes(&x.to_be_bytes()),
+        }
+    }
+
+    fn u32(&mut self, x: u32) {
+        match self.ctx.endianness {
+            Endianness::Little => self.bytes(&x.to_le_bytes()),
+            Endianness::Big => self.bytes(&x.to_be_bytes()),
+        }
+    }
+
+    fn u64(&mut self, x: u64) {
+        match self.ctx.endianness {
+            Endianness::Little => self.bytes(&x.to_le_bytes()),
+            Endianness::Big => self.bytes(&x.to_be_bytes()),
+        }
+    }
+
+    /// Class-sized word, truncated to 32 bits for ELF32
+    fn word(&mut self, x: u64) {
+        match self.ctx.class {
+            Class::Elf32 => self.u32(x as u32),
+            Class::Elf64 => self.u64(x),
+        }
+    }
+
+    fn addr(&mut self, x: Addr) {
+        self.word(x.0)
+    }
+}
+
+/// Copies `bytes` at `offset`, growing `buf` with zeroes as needed. Empty
+/// tables and `NoBits` sections can have any offset, and take no space.
+fn place(buf: &mut Vec<u8>, offset: Addr, bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+    let start: usize = offset.into();
+    let end = start + bytes.len();
+    if buf.len() < end {
+        buf.resize(end, 0);
+    }
+    buf[start..end].copy_from_slice(bytes);
+}
+
+impl Class {
+    pub(crate) fn header_size(self) -> u16 {
+        match self {
+            Class::Elf32 => 52,
+            Class::Elf64 => 64,
+        }
+    }
+
+    pub(crate) fn program_header_size(self) -> u16 {
+        match self {
+            Class::Elf32 => 32,
+            Class::Elf64 => 56,
+        }
+    }
+
+    pub(crate) fn section_header_size(self) -> u16 {
+        match self {
+            Class::Elf32 => 40,
+            Class::Elf64 => 64,
+        }
+    }
+}
+
+impl ProgramHeader {
+    pub fn to_bytes(&self, class: Class, endianness: Endianness) -> Vec<u8> {
+        let mut w = Writer::new(parse::Context { class, endianness });
+        w.u32(self.r#type.into());
+        if class == Class::Elf64 {
+            w.u32(self.flags.bits());
+        }
+        for x in [self.offset, self.vaddr, self.paddr, self.filesz, self.memsz] {
+            w.addr(x);
+        }
+        if class == Class::Elf32 {
+            w.u32(self.flags.bits());
+        }
+        w.addr(self.align);
+        w.out
+    }
+}
+
+impl SectionHeader {
+    pub fn to_bytes(&self, class: Class, endianness: Endianness) -> Vec<u8> {
+        let mut w = Writer::new(parse::Context { class, endianness });
+        w.u32(self.name_offset);
+        w.u32(self.r#type.into());
+        w.word(self.flags.bits() | self.other_flags);
+        for x in [self.addr, self.offset, self.size] {
+            w.addr(x);
+        }
+        w.u32(self.link);
+        w.u32(self.info);
+        w.addr(self.addralign);
+        w.addr(self.entsize);
+        w.out
+    }
+}
+
+impl File {
+    /// Serializes the file back into bytes. Unmodified files come out
+    /// byte-for-byte identical to what they were parsed from.
+    ///
+    /// Only the headers, `gaps` and the `data` of segments and sections are
+    /// written: section data goes first and segment data over it, so changes
+    /// to bytes covered by both must be made to the segment.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (class, endianness) = (self.class, self.endianness);
+        let mut buf = Vec::new();
+
+        for gap in &self.gaps {
+            place(&mut buf, gap.offset, &gap.data);
+        }
+        for sh in &self.section_headers {
+            place(&mut buf, sh.offset, &sh.data);
+        }
+        for ph in &self.program_headers {
+            place(&mut buf, ph.offset, &ph.data);
+        }
+
+        let mut w = Writer::new(self.context());
+        w.bytes(Self::MAGIC);
+        w.u8(class as u8);
+        w.u8(endianness as u8);
+        w.u8(1);
+        w.u8(self.os_abi);
+        w.u8(self.abi_version);
+        w.bytes(&self.padding);
+        w.u16(self.r#type as u16);
+        w.u16(self.machine.into());
+        w.u32(1);
+        w.addr(self.entry_point);
+        w.addr(self.ph_offset);
+        w.addr(self.sh_offset);
+        w.u32(self.flags);
+        w.u16(self.hdr_size);
+        // entries are written at the recorded entry size, unless it is too
+        // small for them: linkers leave it empty when there are no entries
+        let ph_entsize = match self.program_headers.len() {
+            0 => self.ph_entsize,
+            _ => self.ph_entsize.max(class.program_header_size()),
+        };
+        w.u16(ph_entsize);
+        w.u16(self.program_headers.len() as u16);
+        let sh_entsize = match self.section_headers.len() {
+            0 => self.sh_entsize,
+            _ => self.sh_entsize.max(class.section_header_size()),
+        };
+        w.u16(sh_entsize);
+        w.u16(self.section_headers.len() as u16);
+        w.u16(self.sh_nidx);
+        place(&mut buf, Addr(0), &w.out);
+
+        for (index, ph) in self.program_headers.iter().enumerate() {
+            let offset = self.ph_offset + Addr(ph_entsize as u64 * index as u64);
+            place(&mut buf, offset, &ph.to_bytes(class, endianness));
+        }
+        for (index, sh) in self.section_headers.iter().enumerate() {
+            let offset = self.sh_offset + Addr(sh_entsize as u64 * index as u64);
+            place(&mut buf, offset, &sh.to_bytes(class, endianness));
+        }
+
+        buf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Addr, File, ProgramHeader, SegmentContents, SegmentFlag, SegmentType};
+
+    fn assert_round_trip(name: &str, input: &[u8]) {
+        let (_, file) = File::parse(input).unwrap();
+        let output = file.to_bytes();
+        let mismatch = input.iter().zip(&output).position(|(a, b)| a != b);
+        assert_eq!(mismatch, None, "{}: first difference", name);
+        assert_eq!(input.len(), output.len(), "{}: length", name);
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_round_trip("hello", include_bytes!("../../elk/samples/hello"));
+        assert_round_trip("hello.o", include_bytes!("../../elk/samples/hello.o"));
+        assert_round_trip("hello32", include_bytes!("../../elk/samples/hello32"));
+        assert_round_trip("nodata", include_bytes!("../../elk/samples/nodata"));
+        assert_round_trip("nodata.o", include_bytes!("../../elk/samples/nodata.o"));
+        assert_round_trip("entrypoint", include_bytes!("../../elk/samples/entrypoint"));
+        assert_round_trip("crash.core", include_bytes!("../../elk/samples/crash.core"));
+        assert_round_trip("greet", include_bytes!("../../elk/samples/greet"));
+        assert_round_trip(
+            "libgreet.so",
+            include_bytes!("../../elk/samples/libgreet.so"),
+        );
+        assert_round_trip("tls", include_bytes!("../../elk/samples/tls"));
+        assert_round_trip("libtls.so", include_bytes!("../../elk/samples/libtls.so"));
+        assert_round_trip("lines", include_bytes!("../../elk/samples/lines"));
+        assert_round_trip("initfini", include_bytes!("../../elk/samples/initfini"));
+        assert_round_trip(
+            "libinitfini.so",
+            include_bytes!("../../elk/samples/libinitfini.so"),
+        );
+        assert_round_trip("overlap", include_bytes!("../../elk/samples/overlap"));
+
+        use crate::{Class, Machine};
+        for (class, machine) in [
+            (Class::Elf32, Machine::Mips),
+            (Class::Elf64, Machine::PowerPC64),
+        ] {
+            assert_round_trip("big endian", &crate::tests::big_endian_file(class, machine));
+        }
+    }
+
+    #[test]
+    fn non_standard_header_sizes() {
+        let input = include_bytes!("../../elk/samples/hello");
+        let (_, mut file) = File::parse(&input[..]).unwrap();
+
+        // move the section header table to the end, with 8 more bytes per
+        // entry, and fill those and the `e_ident` padding with junk
+        let end = input.len();
+        file.sh_offset = Addr(end as u64);
+        file.sh_entsize = 72;
+        file.padding = [0xee; 7];
+        let mut output = file.to_bytes();
+        output.resize(end + 72 * file.section_headers.len(), 0);
+        for index in 0..file.section_headers.len() {
+            let at = end + 72 * index + 64;
+            output[at..at + 8].copy_from_slice(&[0xaa; 8]);
+        }
+        // and an ELF header that claims to be larger than it is
+        output[52..54].copy_from_slice(&80_u16.to_le_bytes());
+
+        assert_round_trip("non-standard", &output);
+        let (_, file) = File::parse(&output[..]).unwrap();
+        assert_eq!((file.hdr_size, file.sh_entsize), (80, 72));
+        assert_eq!(file.section_by_name(".data").unwrap().data, b"hi there\n");
+    }
+
+    #[test]
+    fn patch() {
+        let input = include_bytes!("../../elk/samples/hello");
+        let (_, mut file) = File::parse(&input[..]).unwrap();
+
+        file.entry_point = Addr(0x401005);
+        file.program_headers[2].flags = SegmentFlag::Read.into();
+
+        // append a note after the existing contents and move the program
+        // header table after it, since it no longer fits in place
+        let note = b"\x04\0\0\0\x04\0\0\0\x01\0\0\0elk\0test".to_vec();
+        let end = Addr(input.len() as u64);
+        file.program_headers.push(ProgramHeader {
+            r#type: SegmentType::Note,
+            flags: SegmentFlag::Read.into(),
+            offset: end,
+            vaddr: Addr(0),
+            paddr: Addr(0),
+            filesz: Addr(note.len() as u64),
+            memsz: Addr(note.len() as u64),
+            align: Addr(4),
+            data: note.clone(),
+            contents: SegmentContents::Unknown,
+        });
+        file.ph_offset = end + Addr(note.len() as u64);
+
+        let output = file.to_bytes();
+        let (_, patched) = File::parse(&output[..]).unwrap();
+        assert_eq!(patched.entry_point, Addr(0x401005));
+        assert_eq!(patched.program_headers.len(), 4);
+        assert_eq!(patched.program_headers[2].flags, SegmentFlag::Read);
+        assert_eq!(patched.program_headers[3].r#type, SegmentType::Note);
+        assert_eq!(patched.program_headers[3].data, note);
+        assert_eq!(
+            patched.section_by_name(".data").unwrap().data,
+            b"hi there\n"
+        );
+    }
+}
@@ -0,0 +1,4 @@
+int answer(int x) {
+        int y = x * 7;
+        return y;
+}
@@ -0,0 +1,9 @@
+@ An unwind table (.ARM.exidx) and build attributes (.ARM.attributes),
+@ both in processor-specific section types
+	.eabi_attribute 67, "2.09"
+	.text
+	.globl f
+	.fnstart
+f:
+	bx lr
+	.fnend
@@ -0,0 +1,6 @@
+        .globl _start
+
+        .text
+
+_start: movq $42, %rax
+        movq %rax, 0    # segfault: nothing is mapped at address 0
@@ -0,0 +1,22 @@
+        # Position-independent executable using libgreet.so, built without
+        # libc. Exits with the length of the greeting.
+
+        .globl _start
+
+        .text
+
+_start: call greet@PLT                  # R_X86_64_JUMP_SLOT
+        call *greet_ptr(%rip)           # R_X86_64_64
+        jmp *exit_ptr(%rip)             # R_X86_64_RELATIVE
+
+exit:   movq greeting_len@GOTPCREL(%rip), %rdi  # R_X86_64_GLOB_DAT
+        movq (%rdi), %rdi               # return code
+        movq $60, %rax                  # exit syscall
+        syscall
+
+        .data
+
+greet_ptr:
+        .quad greet
+exit_ptr:
+        .quad exit
@@ -0,0 +1,17 @@
+        .globl _start
+
+        .text
+
+_start: movl $4, %eax   # write syscall (i386)
+        movl $1, %ebx   # stdout fd
+        movl $msg, %ecx
+        movl $9, %edx   # 8 chars + newline
+        int $0x80
+
+        movl $1, %eax   # exit syscall (i386)
+        xorl %ebx, %ebx # return code 0
+        int $0x80
+
+        .data
+
+msg:    .ascii "hi there\n"
@@ -0,0 +1,50 @@
+# Constructors and destructors of an executable and of the library it needs,
+# run by elk.
+# Without a C library to do it, _start calls the function it is given in rdx
+# to run the destructors. Prints:
+#   lib init, exe init, main, exe fini, lib fini
+    .globl _start
+
+    .text
+print:
+    mov $1, %edi
+    mov $1, %eax
+    syscall
+    ret
+
+exe_init:
+    lea init_msg(%rip), %rsi
+    mov $init_len, %edx
+    jmp print
+
+exe_fini:
+    lea fini_msg(%rip), %rsi
+    mov $fini_len, %edx
+    jmp print
+
+_start:
+    push %rdx
+    lea main_msg(%rip), %rsi
+    mov $main_len, %edx
+    call print
+    pop %rdx
+    test %rdx, %rdx
+    jz 1f
+    call *%rdx
+1:
+    xor %edi, %edi
+    mov $60, %eax
+    syscall
+
+    .section .init_array, "aw"
+    .quad exe_init
+    .section .fini_array, "aw"
+    .quad exe_fini
+
+    .section .rodata
+init_msg:   .ascii "exe init\n"
+    init_len = . - init_msg
+fini_msg:   .ascii "exe fini\n"
+    fini_len = . - fini_msg
+main_msg:   .ascii "main\n"
+    main_len = . - main_msg
@@ -0,0 +1,25 @@
+        # Shared library for greet.s, built without libc
+
+        .globl greeting, greeting_len, greet
+        .type greeting, @object
+        .type greeting_len, @object
+        .size greeting_len, 8
+        .type greet, @function
+
+        .data
+
+greeting:
+.Lgreeting:
+        .ascii "hello from libgreet\n"
+greeting_len:
+.Lgreeting_len:
+        .quad . - .Lgreeting
+
+        .text
+
+greet:  movq $1, %rax                   # write syscall
+        movq $1, %rdi                   # stdout fd
+        leaq .Lgreeting(%rip), %rsi
+        movq .Lgreeting_len(%rip), %rdx
+        syscall
+        ret
@@ -0,0 +1,28 @@
+# A library with a constructor and a destructor, which say when they run
+    .text
+print:
+    mov $1, %edi
+    mov $1, %eax
+    syscall
+    ret
+
+lib_init:
+    lea init_msg(%rip), %rsi
+    mov $init_len, %edx
+    jmp print
+
+lib_fini:
+    lea fini_msg(%rip), %rsi
+    mov $fini_len, %edx
+    jmp print
+
+    .section .init_array, "aw"
+    .quad lib_init
+    .section .fini_array, "aw"
+    .quad lib_fini
+
+    .section .rodata
+init_msg:   .ascii "lib init\n"
+    init_len = . - init_msg
+fini_msg:   .ascii "lib fini\n"
+    fini_len = . - fini_msg
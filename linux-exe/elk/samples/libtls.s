@@ -0,0 +1,23 @@
+# A thread-local counter, accessed with the general dynamic model: through
+# __tls_get_addr, with DTPMOD64 and DTPOFF64 relocations. Not linked against
+# the system's dynamic linker, which defines __tls_get_addr, so this only
+# works under elk.
+    .globl counter, bump
+
+    .section .tdata, "awT", @progbits
+    .align 4
+counter:    .long 40
+
+    .text
+# Increments the counter, and returns its new value
+bump:
+    push %rbx
+    .byte 0x66
+    leaq counter@tlsgd(%rip), %rdi
+    .value 0x6666
+    rex64
+    call __tls_get_addr@PLT
+    incl (%rax)
+    movl (%rax), %eax
+    pop %rbx
+    ret
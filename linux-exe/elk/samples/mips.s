@@ -0,0 +1,7 @@
+# Big-endian, with the processor-specific .reginfo and .MIPS.abiflags
+# sections the assembler adds on its own
+	.text
+	.globl f
+f:
+	jr $ra
+	nop
@@ -0,0 +1,21 @@
+# Text and data share a page: linked with a tiny page size, the segments
+# are only 16-byte aligned. Prints a message from .data and exits with the
+# value of a .bss variable, which must be 0.
+    .globl _start
+    .text
+_start:
+    mov $1, %edi
+    lea msg(%rip), %rsi
+    mov $len, %edx
+    mov $1, %eax
+    syscall
+    mov counter(%rip), %edi
+    mov $60, %eax
+    syscall
+
+    .data
+msg:    .ascii "data!\n"
+    len = . - msg
+
+    .bss
+counter:    .quad 0
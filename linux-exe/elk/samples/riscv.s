@@ -0,0 +1,6 @@
+# Build attributes in .riscv.attributes, a processor-specific section type
+	.attribute arch, "rv64i"
+	.text
+	.globl f
+f:
+	ret
@@ -0,0 +1,42 @@
+# Thread-local variables of an executable (local exec model) and of a
+# library (initial exec model, with a TPOFF64 relocation). Exits with 43 if
+# they read as expected.
+    .globl _start
+
+    .section .tdata, "awT", @progbits
+    .align 8
+two:    .quad 2
+
+    .text
+_start:
+    # the counter starts at 40
+    call bump@PLT
+    cmp $41, %eax
+    jne fail
+
+    # the library and executable agree on where the counter is
+    movq counter@gottpoff(%rip), %rax
+    movl %fs:(%rax), %edi
+    cmp $41, %edi
+    jne fail
+
+    addq %fs:two@tpoff, %rdi
+
+    push %rdi
+    mov $1, %edi
+    lea msg(%rip), %rsi
+    mov $len, %edx
+    mov $1, %eax
+    syscall
+    pop %rdi
+    mov $60, %eax
+    syscall
+
+fail:
+    mov $1, %edi
+    mov $60, %eax
+    syscall
+
+    .section .rodata
+msg:    .ascii "thread-locals ok\n"
+    len = . - msg
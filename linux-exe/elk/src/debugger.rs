@@ -0,0 +1,436 @@
+//! `run --break`: `int3` breakpoints planted in the loaded code, and a small
+//! prompt run from the `SIGTRAP` handler whenever the program hits one.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    error::Error,
+    io::{self, BufRead, Write},
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use delf::{Addr, HexDump, SegmentFlag, SymType, X86_64Registers};
+use iced_x86::FlowControl;
+use libc::{
+    REG_CSGSFS, REG_EFL, REG_R10, REG_R11, REG_R12, REG_R13, REG_R14, REG_R15, REG_R8, REG_R9,
+    REG_RAX, REG_RBP, REG_RBX, REG_RCX, REG_RDI, REG_RDX, REG_RIP, REG_RSI, REG_RSP,
+};
+
+use crate::{disasm, process::Process, tls};
+
+const INT3: u8 = 0xcc;
+/// `eflags` bit making the CPU trap after each instruction
+const TRAP_FLAG: i64 = 0x100;
+/// Longest x86 instruction
+const MAX_INSTRUCTION_LEN: u64 = 15;
+
+/// General-purpose registers saved in the signal frame, restored on return
+/// from the handler
+pub type Gregs = [libc::greg_t; 23];
+
+static DEBUGGER: AtomicPtr<Debugger> = AtomicPtr::new(ptr::null_mut());
+
+const HELP: &str = "\
+regs                 print registers
+x ADDR|REG [LEN]     dump LEN bytes of memory (default 64)
+step                 run one instruction
+next                 run one instruction, stepping over calls
+continue             run until the next breakpoint
+break SYMBOL|ADDR    add a breakpoint
+quit                 stop the program";
+
+/// What to do on the trap following a single step
+struct Step {
+    /// Breakpoint lifted to execute the instruction under it
+    replant: Option<u64>,
+    /// Whether to prompt, or keep going
+    stop: bool,
+}
+
+pub struct Debugger {
+    process: &'static Process,
+    symbol_maps: Vec<delf::SymbolMap>,
+    /// Empty for objects without (readable) `.debug_line`
+    line_tables: Vec<delf::LineTable>,
+    /// Original byte under each planted `int3`
+    breakpoints: HashMap<u64, u8>,
+    /// Breakpoint planted by `next` after a call, lifted at the next stop
+    temporary: Option<u64>,
+    stepping: Option<Step>,
+}
+
+/// Plants a breakpoint at each of `specs` and handles `SIGTRAP`
+pub fn install(process: &'static Process, specs: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut debugger = Debugger {
+        process,
+        symbol_maps: process
+            .objects
+            .iter()
+            .map(|object| object.file.symbol_map())
+            .collect(),
+        line_tables: process
+            .objects
+            .iter()
+            .map(|object| object.file.line_table().unwrap_or_default())
+            .collect(),
+        breakpoints: HashMap::new(),
+        temporary: None,
+        stepping: None,
+    };
+    for spec in specs {
+        let addr = debugger.resolve(spec)?;
+        debugger.plant(addr)?;
+    }
+    DEBUGGER.store(Box::into_raw(Box::new(debugger)), Ordering::SeqCst);
+
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_trap as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO;
+        if libc::sigaction(libc::SIGTRAP, &action, ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+extern "C" fn on_trap(
+    _signal: libc::c_int,
+    _info: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    unsafe {
+        let fs_base = tls::get_fs().unwrap_or(0);
+        tls::with_host_tls(|| {
+            let debugger = &mut *DEBUGGER.load(Ordering::SeqCst);
+            let gregs = &mut (*(context as *mut libc::ucontext_t)).uc_mcontext.gregs;
+            if let Err(e) = debugger.trap(gregs, fs_base) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        })
+    }
+}
+
+/// Writes to code, which is mapped read-only by then
+unsafe fn poke(addr: u64, byte: u8) -> Result<(), region::Error> {
+    let ptr = addr as *mut u8;
+    let protection = region::query(ptr)?.protection();
+    region::protect(ptr, 1, protection | region::Protection::WRITE)?;
+    ptr.write(byte);
+    region::protect(ptr, 1, protection)
+}
+
+/// Whether `len` bytes from `addr` can be read without faulting
+fn readable(addr: u64, len: u64) -> bool {
+    let page_size = region::page::size() as u64;
+    let Some(end) = addr.checked_add(len.max(1)) else {
+        return false;
+    };
+    (crate::process::align_down(addr, page_size)..end)
+        .step_by(page_size as usize)
+        .all(|page| region::query(page as *const u8).is_ok_and(|r| r.is_readable()))
+}
+
+impl Debugger {
+    /// A `0x`-prefixed address, or a function defined by any object
+    fn resolve(&self, spec: &str) -> Result<u64, Box<dyn Error>> {
+        let addr = if spec.starts_with("0x") {
+            crate::parse_addr(spec)?.0
+        } else {
+            self.process
+                .objects
+                .iter()
+                .find_map(|object| {
+                    object
+                        .file
+                        .symbols()
+                        .chain(object.file.dynamic_symbols())
+                        .find(|sym| {
+                            sym.name == spec
+                                && !sym.shndx.is_undef()
+                                && matches!(sym.r#type, SymType::Func | SymType::NoType)
+                        })
+                        .map(|sym| (object.base + sym.value).0)
+                })
+                .ok_or_else(|| format!("no function named {:?}", spec))?
+        };
+        let executable = self.process.objects.iter().any(|object| {
+            addr.checked_sub(object.base.0)
+                .and_then(|addr| object.file.segment_at(Addr(addr)))
+                .is_some_and(|ph| ph.flags.contains(SegmentFlag::Execute))
+        });
+        if !executable {
+            return Err(format!("{:?} is not in loaded code", Addr(addr)).into());
+        }
+        Ok(addr)
+    }
+
+    fn plant(&mut self, addr: u64) -> Result<(), region::Error> {
+        if let Entry::Vacant(entry) = self.breakpoints.entry(addr) {
+            entry.insert(unsafe { *(addr as *const u8) });
+            unsafe { poke(addr, INT3)? };
+        }
+        Ok(())
+    }
+
+    fn lift(&mut self, addr: u64) -> Result<(), region::Error> {
+        if let Some(byte) = self.breakpoints.remove(&addr) {
+            unsafe { poke(addr, byte)? };
+        }
+        Ok(())
+    }
+
+    fn trap(&mut self, gregs: &mut Gregs, fs_base: u64) -> Result<(), Box<dyn Error>> {
+        if let Some(step) = self.stepping.take() {
+            gregs[REG_EFL as usize] &= !TRAP_FLAG;
+            if let Some(addr) = step.replant {
+                unsafe { poke(addr, INT3)? };
+            }
+            if !step.stop {
+                return Ok(());
+            }
+        } else {
+            let addr = gregs[REG_RIP as usize] as u64 - 1;
+            if !self.breakpoints.contains_key(&addr) {
+                // an `int3` of the program's own
+                return Ok(());
+            }
+            gregs[REG_RIP as usize] = addr as i64;
+            if Some(addr) != self.temporary {
+                eprintln!("Breakpoint at {}", self.describe(addr));
+            }
+        }
+        if let Some(addr) = self.temporary.take() {
+            self.lift(addr)?;
+        }
+        self.prompt(gregs, fs_base)
+    }
+
+    fn prompt(&mut self, gregs: &mut Gregs, fs_base: u64) -> Result<(), Box<dyn Error>> {
+        let rip = gregs[REG_RIP as usize] as u64;
+        let next = self.instruction_at(rip);
+        match &next {
+            Some(ins) => eprintln!("=> {:?}  {}", Addr(rip), ins.text),
+            None => eprintln!("=> {:?}", Addr(rip)),
+        }
+
+        let stdin = io::stdin();
+        loop {
+            eprint!("(elk) ");
+            io::stderr().flush()?;
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line)? == 0 {
+                // nobody to answer, let the program finish
+                return self.resume(gregs, false);
+            }
+            let mut words = line.split_whitespace();
+            match words.next() {
+                None => {}
+                Some("regs" | "r") => eprintln!("{:?}", registers(gregs, fs_base)),
+                Some("x") => match (words.next(), words.next()) {
+                    (Some(addr), len) => {
+                        let len = match len.map(|len| len.parse::<u64>()) {
+                            None => Ok(64),
+                            Some(len) => len,
+                        };
+                        match (parse_operand(addr, gregs), len) {
+                            (Ok(addr), Ok(len)) => dump(addr, len),
+                            (Err(e), _) => eprintln!("{}", e),
+                            (_, Err(e)) => eprintln!("invalid length: {}", e),
+                        }
+                    }
+                    (None, _) => eprintln!("usage: x ADDR|REG [LEN]"),
+                },
+                Some("step" | "s") => return self.resume(gregs, true),
+                Some("next" | "n") => match &next {
+                    Some(ins) if ins.is_call => {
+                        let after = rip + ins.len as u64;
+                        if !self.breakpoints.contains_key(&after) {
+                            self.plant(after)?;
+                            self.temporary = Some(after);
+                        }
+                        return self.resume(gregs, false);
+                    }
+                    _ => return self.resume(gregs, true),
+                },
+                Some("continue" | "c") => return self.resume(gregs, false),
+                Some("break" | "b") => match words.next().map(|spec| self.resolve(spec)) {
+                    Some(Ok(addr)) => {
+                        self.plant(addr)?;
+                        eprintln!("Breakpoint at {}", self.describe(addr));
+                    }
+                    Some(Err(e)) => eprintln!("{}", e),
+                    None => eprintln!("usage: break SYMBOL|ADDR"),
+                },
+                Some("quit" | "q") => std::process::exit(1),
+                Some("help" | "h" | "?") => eprintln!("{}", HELP),
+                Some(cmd) => eprintln!("unknown command {:?}, try \"help\"", cmd),
+            }
+        }
+    }
+
+    /// Lets the program go on, stepping over the breakpoint at `rip` if
+    /// there is one, and stopping after one instruction if `stop`
+    fn resume(&mut self, gregs: &mut Gregs, stop: bool) -> Result<(), Box<dyn Error>> {
+        let rip = gregs[REG_RIP as usize] as u64;
+        let replant = match self.breakpoints.get(&rip) {
+            Some(&byte) => {
+                unsafe { poke(rip, byte)? };
+                Some(rip)
+            }
+            None => None,
+        };
+        if stop || replant.is_some() {
+            gregs[REG_EFL as usize] |= TRAP_FLAG;
+            self.stepping = Some(Step { replant, stop });
+        }
+        Ok(())
+    }
+
+    /// `addr` as `path <symbol+offset>`
+    fn describe(&self, addr: u64) -> String {
+        let mut res = format!("{:?}", Addr(addr));
+        if let Some(i) = self.object_at(addr) {
+            let object = &self.process.objects[i];
+            if let Some((sym, offset)) = self.symbol_maps[i].lookup(Addr(addr) - object.base) {
+                match offset {
+                    0 => res += &format!(" <{}>", sym.name),
+                    offset => res += &format!(" <{}+{:#x}>", sym.name, offset),
+                }
+            }
+            res += &format!(" in {}", object.path.display());
+            if let Some((path, line)) = self.line_tables[i].lookup(Addr(addr) - object.base) {
+                res += &format!(" at {}:{}", path.display(), line);
+            }
+        }
+        res
+    }
+
+    fn object_at(&self, addr: u64) -> Option<usize> {
+        self.process.objects.iter().position(|object| {
+            (object.base + object.mem_range.start).0 <= addr
+                && addr < (object.base + object.mem_range.end).0
+        })
+    }
+
+    /// Decodes the instruction at `addr` as it was before planting
+    /// breakpoints. Addresses in operands are relative to the object's base,
+    /// as in `elk disasm`.
+    fn instruction_at(&self, addr: u64) -> Option<Next> {
+        let i = self.object_at(addr)?;
+        let object = &self.process.objects[i];
+        let end = (object.base + object.mem_range.end).0;
+        let len = MAX_INSTRUCTION_LEN.min(end - addr);
+        if !readable(addr, len) {
+            return None;
+        }
+        let mut code =
+            unsafe { std::slice::from_raw_parts(addr as *const u8, len as usize) }.to_vec();
+        for (i, byte) in code.iter_mut().enumerate() {
+            if let Some(&original) = self.breakpoints.get(&(addr + i as u64)) {
+                *byte = original;
+            }
+        }
+        let origin = Addr(addr) - object.base;
+        let ins = disasm::disassemble(&object.file, &self.symbol_maps[i], &code, origin)
+            .ok()?
+            .into_iter()
+            .next()?;
+        Some(Next {
+            is_call: matches!(
+                ins.flow_control,
+                FlowControl::Call | FlowControl::IndirectCall
+            ),
+            len: ins.bytes.len(),
+            text: ins.text,
+        })
+    }
+}
+
+/// The instruction about to run
+struct Next {
+    text: String,
+    len: usize,
+    is_call: bool,
+}
+
+const REGISTERS: [(&str, libc::c_int); 18] = [
+    ("rax", REG_RAX),
+    ("rbx", REG_RBX),
+    ("rcx", REG_RCX),
+    ("rdx", REG_RDX),
+    ("rsi", REG_RSI),
+    ("rdi", REG_RDI),
+    ("rbp", REG_RBP),
+    ("rsp", REG_RSP),
+    ("r8", REG_R8),
+    ("r9", REG_R9),
+    ("r10", REG_R10),
+    ("r11", REG_R11),
+    ("r12", REG_R12),
+    ("r13", REG_R13),
+    ("r14", REG_R14),
+    ("r15", REG_R15),
+    ("rip", REG_RIP),
+    ("eflags", REG_EFL),
+];
+
+/// A register name, or an address in hex
+fn parse_operand(s: &str, gregs: &Gregs) -> Result<u64, String> {
+    match REGISTERS.iter().find(|(name, _)| *name == s) {
+        Some(&(_, reg)) => Ok(gregs[reg as usize] as u64),
+        None => crate::parse_addr(s).map(|addr| addr.0),
+    }
+}
+
+pub fn registers(gregs: &Gregs, fs_base: u64) -> X86_64Registers {
+    let reg = |reg: libc::c_int| gregs[reg as usize] as u64;
+    // cs, gs and fs, 16 bits each
+    let segments = reg(REG_CSGSFS);
+    X86_64Registers {
+        r15: reg(REG_R15),
+        r14: reg(REG_R14),
+        r13: reg(REG_R13),
+        r12: reg(REG_R12),
+        rbp: reg(REG_RBP),
+        rbx: reg(REG_RBX),
+        r11: reg(REG_R11),
+        r10: reg(REG_R10),
+        r9: reg(REG_R9),
+        r8: reg(REG_R8),
+        rax: reg(REG_RAX),
+        rcx: reg(REG_RCX),
+        rdx: reg(REG_RDX),
+        rsi: reg(REG_RSI),
+        rdi: reg(REG_RDI),
+        orig_rax: u64::MAX,
+        rip: reg(REG_RIP),
+        cs: segments & 0xffff,
+        eflags: reg(REG_EFL),
+        rsp: reg(REG_RSP),
+        ss: 0,
+        fs_base,
+        gs_base: 0,
+        ds: 0,
+        es: 0,
+        fs: (segments >> 32) & 0xffff,
+        gs: (segments >> 16) & 0xffff,
+    }
+}
+
+/// Prints `len` bytes from `addr`, 16 per line
+fn dump(addr: u64, len: u64) {
+    if !readable(addr, len) {
+        eprintln!(
+            "cannot read {:?}..{:?}",
+            Addr(addr),
+            Addr(addr.wrapping_add(len))
+        );
+        return;
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(addr as *const u8, len as usize) };
+    for (i, row) in bytes.chunks(16).enumerate() {
+        eprintln!("{:?}  {:?}", Addr(addr + i as u64 * 16), HexDump(row));
+    }
+}
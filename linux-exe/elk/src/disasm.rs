@@ -0,0 +1,118 @@
+use std::{collections::HashMap, error::Error};
+
+use iced_x86::{
+    Decoder, DecoderOptions, FlowControl, Formatter, IntelFormatter, OpKind, SymbolResolver,
+    SymbolResult,
+};
+
+pub struct Instruction {
+    pub addr: delf::Addr,
+    pub bytes: Vec<u8>,
+    pub text: String,
+    /// Whether it branches, calls, returns... for stepping over calls
+    pub flow_control: FlowControl,
+    /// Symbol starting at this instruction, if any
+    pub label: Option<String>,
+}
+
+/// Names branch targets and `rip`-relative operands, from names looked up
+/// ahead of formatting
+struct Symbols(HashMap<u64, String>);
+
+impl SymbolResolver for Symbols {
+    fn symbol(
+        &mut self,
+        instruction: &iced_x86::Instruction,
+        _operand: u32,
+        instruction_operand: Option<u32>,
+        address: u64,
+        _address_size: u32,
+    ) -> Option<SymbolResult<'_>> {
+        target(instruction, instruction_operand?)?;
+        let name = self.0.get(&address)?;
+        Some(SymbolResult::with_str(address, name))
+    }
+}
+
+/// The address an operand refers to, for branches and `rip`-relative
+/// operands only: immediates and other displacements are more often plain
+/// numbers
+fn target(ins: &iced_x86::Instruction, operand: u32) -> Option<u64> {
+    match ins.op_kind(operand) {
+        OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64 => {
+            Some(ins.near_branch_target())
+        }
+        OpKind::Memory if ins.is_ip_rel_memory_operand() => Some(ins.ip_rel_memory_address()),
+        _ => None,
+    }
+}
+
+/// Disassembles `code`, loaded at `origin` in `file`, annotating it with
+/// `symbols`, from `File::symbol_map`
+pub fn disassemble(
+    file: &delf::File,
+    symbols: &delf::SymbolMap,
+    code: &[u8],
+    origin: delf::Addr,
+) -> Result<Vec<Instruction>, Box<dyn Error>> {
+    let bitness = match (file.machine, file.class) {
+        (delf::Machine::X86 | delf::Machine::X86_64, delf::Class::Elf32) => 32,
+        (delf::Machine::X86 | delf::Machine::X86_64, delf::Class::Elf64) => 64,
+        _ => {
+            return Err(format!(
+                "cannot disassemble {:?} code, only x86 and x86-64",
+                file.machine
+            )
+            .into())
+        }
+    };
+    let instructions = Decoder::with_ip(bitness, code, origin.0, DecoderOptions::NONE)
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let mut names = HashMap::new();
+    for ins in &instructions {
+        for addr in (0..ins.op_count()).filter_map(|i| target(ins, i)) {
+            names.entry(addr).or_insert_with(|| {
+                symbols
+                    .lookup(delf::Addr(addr))
+                    .map(|(sym, offset)| match offset {
+                        0 => sym.name.clone(),
+                        offset => format!("{}+{:#x}", sym.name, offset),
+                    })
+            });
+        }
+    }
+    let names = names
+        .into_iter()
+        .filter_map(|(addr, name)| Some((addr, name?)))
+        .collect();
+
+    let mut formatter = IntelFormatter::with_options(Some(Box::new(Symbols(names))), None);
+    let options = formatter.options_mut();
+    options.set_hex_prefix("0x");
+    options.set_hex_suffix("");
+    options.set_uppercase_hex(false);
+    options.set_branch_leading_zeros(false);
+    let mut res = Vec::new();
+    for ins in instructions {
+        let mut text = String::new();
+        formatter.format(&ins, &mut text);
+        let start = (ins.ip() - origin.0) as usize;
+        res.push(Instruction {
+            addr: delf::Addr(ins.ip()),
+            bytes: code[start..start + ins.len()].to_vec(),
+            text,
+            flow_control: ins.flow_control(),
+            label: symbols
+                .at(delf::Addr(ins.ip()))
+                .iter()
+                .find(|sym| {
+                    !sym.name.is_empty()
+                        && matches!(sym.r#type, delf::SymType::Func | delf::SymType::NoType)
+                })
+                .map(|sym| sym.name.clone()),
+        });
+    }
+    Ok(res)
+}
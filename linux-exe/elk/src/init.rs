@@ -0,0 +1,122 @@
+//! Constructors and destructors: `DT_PREINIT_ARRAY`, `DT_INIT`,
+//! `DT_INIT_ARRAY`, `DT_FINI_ARRAY` and `DT_FINI`.
+
+use std::sync::OnceLock;
+
+use delf::{Addr, DynamicTag};
+
+use crate::process::{Object, Process};
+
+/// What `run_finalizers` calls, in order
+static FINALIZERS: OnceLock<Vec<Addr>> = OnceLock::new();
+
+type InitFn = extern "C" fn(i32, *const *const u8, *const *const u8);
+
+/// Functions to call before jumping to the object at `index`: its
+/// `DT_PREINIT_ARRAY`, then the `DT_INIT` and `DT_INIT_ARRAY` of each object
+/// in `Process::init_order`
+pub fn initializers(process: &Process, index: usize) -> Vec<Addr> {
+    let mut res = read_array(
+        &process.objects[index],
+        DynamicTag::PreInitArray,
+        DynamicTag::PreInitArraySz,
+    );
+    for i in process.init_order(index) {
+        let object = &process.objects[i];
+        if let Some(init) = object.file.dynamic_entry(DynamicTag::Init) {
+            res.push(object.base + init);
+        }
+        res.extend(read_array(
+            object,
+            DynamicTag::InitArray,
+            DynamicTag::InitArraySz,
+        ));
+    }
+    res
+}
+
+/// Functions to call on exit: the `DT_FINI_ARRAY`, backwards, then the
+/// `DT_FINI` of each object, in the reverse of `Process::init_order`
+pub fn finalizers(process: &Process, index: usize) -> Vec<Addr> {
+    let mut res = Vec::new();
+    for i in process.init_order(index).into_iter().rev() {
+        let object = &process.objects[i];
+        res.extend(
+            read_array(object, DynamicTag::FiniArray, DynamicTag::FiniArraySz)
+                .into_iter()
+                .rev(),
+        );
+        if let Some(fini) = object.file.dynamic_entry(DynamicTag::Fini) {
+            res.push(object.base + fini);
+        }
+    }
+    res
+}
+
+/// Reads a relocated array of function pointers
+fn read_array(object: &Object, array: DynamicTag, size: DynamicTag) -> Vec<Addr> {
+    let (array, size) = match (
+        object.file.dynamic_entry(array),
+        object.file.dynamic_entry(size),
+    ) {
+        (Some(array), Some(size)) => (array, size),
+        _ => return Vec::new(),
+    };
+    let array = (object.base + array).0 as *const u64;
+    (0..size.0 as usize / 8)
+        .map(|i| unsafe { array.add(i).read_unaligned() })
+        // both mean "nothing" to glibc
+        .filter(|&f| f != 0 && f != u64::MAX)
+        .map(Addr)
+        .collect()
+}
+
+/// glibc's `__ctype_init`, if the C library is glibc. Its dynamic linker
+/// calls it, through `__libc_early_init`, before any initializer: until
+/// then, the thread's `ctype` tables used by `isalpha` and co. are null.
+/// The rest of `__libc_early_init` needs more of the dynamic linker's state
+/// than elk sets up.
+pub fn ctype_init(process: &Process) -> Option<Addr> {
+    process.symbol_addr("__ctype_init")
+}
+
+/// Calls `__ctype_init`.
+///
+/// # Safety
+///
+/// Same as `run_initializers`, which must come after it.
+pub unsafe fn run_ctype_init(f: Addr) {
+    let f: extern "C" fn() = std::mem::transmute(f.0);
+    f();
+}
+
+/// Calls `initializers` with the arguments and environment of the stack the
+/// program starts with.
+///
+/// # Safety
+///
+/// The program must be ready to run, its TLS installed.
+pub unsafe fn run_initializers(initializers: &[Addr], stack_pointer: u64) {
+    let argc = *(stack_pointer as *const u64);
+    let argv = (stack_pointer + 8) as *const *const u8;
+    let envp = argv.add(argc as usize + 1);
+    for f in initializers {
+        let f: InitFn = std::mem::transmute(f.0);
+        f(argc as i32, argv, envp);
+    }
+}
+
+/// Sets what `run_finalizers` calls
+pub fn set_finalizers(finalizers: Vec<Addr>) {
+    // elk only ever runs one program
+    let _ = FINALIZERS.set(finalizers);
+}
+
+/// Handed to the program in `rdx` on entry, for its C library to register
+/// with `atexit`, like the system's dynamic linker does
+pub extern "C" fn run_finalizers() {
+    for f in FINALIZERS.get().into_iter().flatten() {
+        let f: extern "C" fn() = unsafe { std::mem::transmute(f.0) };
+        f();
+    }
+}
@@ -0,0 +1,85 @@
+//! Lazy binding: until their `JUMP_SLOT` relocation is resolved, PLT entries
+//! jump to the PLT header, which pushes the object's GOT[1] and jumps to its
+//! GOT[2], `trampoline`.
+
+use std::{
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use crate::{process::Process, tls};
+
+static PROCESS: AtomicPtr<Process> = AtomicPtr::new(ptr::null_mut());
+
+/// Makes `process` the one `trampoline` resolves symbols for
+pub fn install(process: &'static Process) {
+    PROCESS.store(process as *const _ as *mut _, Ordering::SeqCst);
+}
+
+/// Called by the PLT header, with the index of the object and of the
+/// relocation on the stack. Saves the argument registers, binds the symbol
+/// and jumps to it as if it had been called directly.
+#[unsafe(naked)]
+pub extern "C" fn trampoline() {
+    std::arch::naked_asm!(
+        "push rbp",
+        "mov rbp, rsp",
+        "and rsp, -16",
+        "sub rsp, 0xc0",
+        "mov [rsp], rax",
+        "mov [rsp + 0x08], rdi",
+        "mov [rsp + 0x10], rsi",
+        "mov [rsp + 0x18], rdx",
+        "mov [rsp + 0x20], rcx",
+        "mov [rsp + 0x28], r8",
+        "mov [rsp + 0x30], r9",
+        "movdqa [rsp + 0x40], xmm0",
+        "movdqa [rsp + 0x50], xmm1",
+        "movdqa [rsp + 0x60], xmm2",
+        "movdqa [rsp + 0x70], xmm3",
+        "movdqa [rsp + 0x80], xmm4",
+        "movdqa [rsp + 0x90], xmm5",
+        "movdqa [rsp + 0xa0], xmm6",
+        "movdqa [rsp + 0xb0], xmm7",
+        "mov rdi, [rbp + 8]",
+        "mov rsi, [rbp + 16]",
+        "call {resolve}",
+        "mov r11, rax",
+        "mov rax, [rsp]",
+        "mov rdi, [rsp + 0x08]",
+        "mov rsi, [rsp + 0x10]",
+        "mov rdx, [rsp + 0x18]",
+        "mov rcx, [rsp + 0x20]",
+        "mov r8, [rsp + 0x28]",
+        "mov r9, [rsp + 0x30]",
+        "movdqa xmm0, [rsp + 0x40]",
+        "movdqa xmm1, [rsp + 0x50]",
+        "movdqa xmm2, [rsp + 0x60]",
+        "movdqa xmm3, [rsp + 0x70]",
+        "movdqa xmm4, [rsp + 0x80]",
+        "movdqa xmm5, [rsp + 0x90]",
+        "movdqa xmm6, [rsp + 0xa0]",
+        "movdqa xmm7, [rsp + 0xb0]",
+        "mov rsp, rbp",
+        "pop rbp",
+        // the object and relocation indices
+        "add rsp, 16",
+        "jmp r11",
+        resolve = sym resolve,
+    )
+}
+
+extern "C" fn resolve(object: u64, rela: u64) -> u64 {
+    unsafe {
+        tls::with_host_tls(|| {
+            let process = &*PROCESS.load(Ordering::SeqCst);
+            match process.bind_lazily(object as usize, rela as usize) {
+                Ok(addr) => addr.0,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        })
+    }
+}
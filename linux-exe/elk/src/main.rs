@@ -1,94 +1,675 @@
-use std::{env, error::Error, fs};
+mod debugger;
+mod disasm;
+mod init;
+mod lazy;
+mod process;
+mod sandbox;
+mod stack;
+mod tls;
 
-use mmap::{MapOption, MemoryMap};
-use region::{protect, Protection};
+use std::{
+    env,
+    error::Error,
+    ffi::OsString,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let input_path = env::args().nth(1).expect("Usage: elk FILE");
-    let input = fs::read(&input_path)?;
+use argh::FromArgs;
+use serde_json::{json, Value};
 
-    println!("Analyzing {:?}...", input_path);
+/// Inspect ELF files, or load and run them
+#[derive(FromArgs)]
+struct Args {
+    #[argh(subcommand)]
+    command: Command,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Info(InfoArgs),
+    Readelf(ReadelfArgs),
+    Diff(DiffArgs),
+    Checksec(ChecksecArgs),
+    Symbols(SymbolsArgs),
+    Deps(DepsArgs),
+    Disasm(DisasmArgs),
+    Run(RunArgs),
+}
+
+/// Print the header, segments, sections and notes of a file
+#[derive(FromArgs)]
+#[argh(subcommand, name = "info")]
+struct InfoArgs {
+    /// print JSON instead of text
+    #[argh(switch)]
+    json: bool,
+    /// the ELF file
+    #[argh(positional)]
+    file: PathBuf,
+}
+
+/// Print the sections, segments, dynamic table and symbols of a file like
+/// `readelf -lSdsW`
+#[derive(FromArgs)]
+#[argh(subcommand, name = "readelf")]
+struct ReadelfArgs {
+    /// print everything delf parsed as JSON instead, for diffing
+    #[argh(switch)]
+    json: bool,
+    /// the ELF file
+    #[argh(positional)]
+    file: PathBuf,
+}
 
-    let file = match delf::File::parse_or_print_error(&input[..]) {
-        Some(f) => f,
-        None => std::process::exit(1),
+/// Compare two versions of a file: segments, sections and symbols added,
+/// removed, resized or given other flags, and the entry point
+#[derive(FromArgs)]
+#[argh(subcommand, name = "diff")]
+struct DiffArgs {
+    /// print JSON instead of text
+    #[argh(switch)]
+    json: bool,
+    /// the old version
+    #[argh(positional)]
+    old: PathBuf,
+    /// the new version
+    #[argh(positional)]
+    new: PathBuf,
+}
+
+/// Report the exploit mitigations of files, like `checksec`: PIE, NX stack,
+/// RELRO, stack canaries, FORTIFY, RPATH/RUNPATH and W+X segments
+#[derive(FromArgs)]
+#[argh(subcommand, name = "checksec")]
+struct ChecksecArgs {
+    /// print JSON instead of text
+    #[argh(switch)]
+    json: bool,
+    /// the ELF files
+    #[argh(positional)]
+    files: Vec<PathBuf>,
+}
+
+/// List the symbols of a file
+#[derive(FromArgs)]
+#[argh(subcommand, name = "symbols")]
+struct SymbolsArgs {
+    /// list `.dynsym` instead of `.symtab`
+    #[argh(switch)]
+    dynamic: bool,
+    /// print JSON instead of text
+    #[argh(switch)]
+    json: bool,
+    /// the ELF file
+    #[argh(positional)]
+    file: PathBuf,
+}
+
+/// Find the libraries a file needs, recursively, without loading them
+#[derive(FromArgs)]
+#[argh(subcommand, name = "deps")]
+struct DepsArgs {
+    /// colon-separated directories to search first, like `LD_LIBRARY_PATH`
+    #[argh(option)]
+    library_path: Option<OsString>,
+    /// print JSON instead of text
+    #[argh(switch)]
+    json: bool,
+    /// the ELF file
+    #[argh(positional)]
+    file: PathBuf,
+}
+
+/// Disassemble the executable sections of a file, or part of them
+#[derive(FromArgs)]
+#[argh(subcommand, name = "disasm")]
+struct DisasmArgs {
+    /// disassemble this symbol only
+    #[argh(option)]
+    symbol: Option<String>,
+    /// disassemble this address range only, as START..END in hex
+    #[argh(option, from_str_fn(parse_addr_range))]
+    range: Option<Range<delf::Addr>>,
+    /// print JSON instead of text
+    #[argh(switch)]
+    json: bool,
+    /// the ELF file
+    #[argh(positional)]
+    file: PathBuf,
+}
+
+/// Load a program and its dependencies into elk, and run it
+#[derive(FromArgs)]
+#[argh(subcommand, name = "run")]
+struct RunArgs {
+    /// colon-separated directories to search first, like `LD_LIBRARY_PATH`
+    #[argh(option)]
+    library_path: Option<OsString>,
+    /// where to load a position-independent program, in hex
+    #[argh(option, from_str_fn(parse_addr))]
+    base: Option<delf::Addr>,
+    /// describe the loading on stderr
+    #[argh(switch, short = 'v')]
+    verbose: bool,
+    /// bind all functions at load time rather than on first call, also
+    /// enabled by a non-empty `LD_BIND_NOW`
+    #[argh(switch)]
+    bind_now: bool,
+    /// log each symbol resolution, with the object needing it and the one
+    /// providing it, on stderr
+    #[argh(switch)]
+    trace_bindings: bool,
+    /// stop at a function or a 0x-prefixed address and open a debugger
+    /// prompt, can be repeated
+    #[argh(option, long = "break")]
+    breaks: Vec<String>,
+    /// load and run the program in a child process, and on a crash report
+    /// the signal and registers
+    #[argh(switch)]
+    sandbox: bool,
+    /// deny the program starting other programs, tracing and networking
+    #[argh(switch)]
+    seccomp: bool,
+    /// the program
+    #[argh(positional)]
+    file: PathBuf,
+    /// arguments for the program, after `--` if any start with a dash
+    #[argh(positional, greedy)]
+    args: Vec<String>,
+}
+
+fn parse_addr(s: &str) -> Result<delf::Addr, String> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16)
+        .map(delf::Addr)
+        .map_err(|e| format!("invalid address {:?}: {}", s, e))
+}
+
+fn parse_addr_range(s: &str) -> Result<Range<delf::Addr>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| format!("invalid range {:?}, expected START..END", s))?;
+    Ok(parse_addr(start)?..parse_addr(end)?)
+}
+
+fn main() {
+    let args: Args = argh::from_env();
+    let res = match args.command {
+        Command::Info(args) => cmd_info(args),
+        Command::Readelf(args) => cmd_readelf(args),
+        Command::Diff(args) => cmd_diff(args),
+        Command::Checksec(args) => cmd_checksec(args),
+        Command::Symbols(args) => cmd_symbols(args),
+        Command::Deps(args) => cmd_deps(args),
+        Command::Disasm(args) => cmd_disasm(args),
+        Command::Run(args) => cmd_run(args),
     };
+    if let Err(e) = res {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Parses a whole file, or exits describing what is wrong with it
+fn read_file(path: &Path) -> Result<delf::File, Box<dyn Error>> {
+    let input = fs::read(path)?;
+    match delf::File::from_bytes(&input[..]) {
+        Ok(file) => Ok(file),
+        Err(err) => {
+            eprintln!("Parsing failed: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn print_json(value: &Value) -> Result<(), Box<dyn Error>> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn cmd_info(args: InfoArgs) -> Result<(), Box<dyn Error>> {
+    let file = read_file(&args.file)?;
+    let entry_symbol = file.symbol_for_addr(file.entry_point);
+
+    if args.json {
+        return print_json(&info_json(&file, entry_symbol));
+    }
+
     println!("{:#?}", file);
+    match entry_symbol {
+        Some((sym, 0)) => println!("Entry point: {:?} <{}>", file.entry_point, sym.name),
+        Some((sym, offset)) => println!(
+            "Entry point: {:?} <{}+{:#x}>",
+            file.entry_point, sym.name, offset
+        ),
+        None => println!("Entry point: {:?}", file.entry_point),
+    }
+    for lib in file.needed_libraries() {
+        println!("Needs {:?}", lib);
+    }
+    if let Some(build_id) = file.build_id() {
+        println!("Build ID: {}", build_id);
+    }
+    if file.r#type == delf::Type::Core {
+        print_core_summary(&file);
+    }
+    Ok(())
+}
 
-    println!("Disassembling {:?}...", input_path);
-    let code_ph = file
+fn info_json(file: &delf::File, entry_symbol: Option<(delf::Sym, u64)>) -> Value {
+    let program_headers = file
         .program_headers
         .iter()
-        .find(|ph| ph.mem_range().contains(&file.entry_point))
-        .expect("segment with entry point not found");
+        .map(|ph| {
+            json!({
+                "type": format!("{:?}", ph.r#type),
+                "flags": ph.flags.iter().map(|f| format!("{:?}", f)).collect::<Vec<_>>(),
+                "offset": ph.offset.0,
+                "vaddr": ph.vaddr.0,
+                "filesz": ph.filesz.0,
+                "memsz": ph.memsz.0,
+                "align": ph.align.0,
+            })
+        })
+        .collect::<Vec<_>>();
+    let section_headers = file
+        .section_headers
+        .iter()
+        .map(|sh| {
+            json!({
+                "name": sh.name,
+                "type": format!("{:?}", sh.r#type),
+                "flags": sh.flags.iter().map(|f| format!("{:?}", f)).collect::<Vec<_>>(),
+                "addr": sh.addr.0,
+                "offset": sh.offset.0,
+                "size": sh.size.0,
+            })
+        })
+        .collect::<Vec<_>>();
 
-    ndisasm(&code_ph.data[..], file.entry_point)?;
+    let mut res = json!({
+        "class": format!("{:?}", file.class),
+        "endianness": format!("{:?}", file.endianness),
+        "type": format!("{:?}", file.r#type),
+        "machine": format!("{:?}", file.machine),
+        "entry_point": file.entry_point.0,
+        "entry_symbol": entry_symbol.map(|(sym, offset)| json!({
+            "name": sym.name,
+            "offset": offset,
+        })),
+        "program_headers": program_headers,
+        "section_headers": section_headers,
+        "needed": file.needed_libraries(),
+        "soname": file.soname(),
+        "rpath": file.rpath(),
+        "runpath": file.runpath(),
+        "build_id": file.build_id().map(|id| id.to_string()),
+    });
 
-    println!("Mapping {:?} in memory...", input_path);
-    let mut mappings = Vec::new();
+    if file.r#type == delf::Type::Core {
+        res["core"] = json!({
+            "process": file.process_info().map(|info| json!({
+                "pid": info.pid,
+                "filename": info.filename,
+                "args": info.args,
+            })),
+            "signal": file.crash_signal(),
+            "threads": file.threads().iter().map(|thread| json!({
+                "pid": thread.pid,
+                "rip": thread.registers.rip,
+                "rsp": thread.registers.rsp,
+            })).collect::<Vec<_>>(),
+            "mapped_files": file.mapped_files().iter().map(|mapped| json!({
+                "start": mapped.start.0,
+                "end": mapped.end.0,
+                "offset": mapped.offset.0,
+                "path": mapped.path,
+            })).collect::<Vec<_>>(),
+        });
+    }
+    res
+}
 
-    for ph in file
-        .program_headers
-        .iter()
-        .filter(|ph| ph.r#type == delf::SegmentType::Load)
-    {
-        println!("Mapping segment @ {:?} with {:?}", ph.mem_range(), ph.flags);
-        let mem_range = ph.mem_range();
-        let len: usize = (mem_range.end - mem_range.start).into();
-        let addr: *mut u8 = mem_range.start.0 as _;
-        let map = MemoryMap::new(len, &[MapOption::MapWritable, MapOption::MapAddr(addr)])?;
-        println!("Copying segment data");
-        {
-            let dst = unsafe { std::slice::from_raw_parts_mut(addr, ph.data.len()) };
-            dst.copy_from_slice(&ph.data[..]);
+fn print_core_summary(file: &delf::File) {
+    if let Some(info) = file.process_info() {
+        println!("Process {} ({:?}): {}", info.pid, info.filename, info.args);
+    }
+    if let Some(signal) = file.crash_signal() {
+        println!("Killed by signal {}", signal);
+    }
+    for thread in file.threads() {
+        println!("Thread {}: {:?}", thread.pid, thread.registers);
+        let rip = delf::Addr(thread.registers.rip);
+        if let Some(mapped) = file.mapped_file_at(rip) {
+            println!("    at {:?} in {}", rip, mapped.path);
         }
+    }
+    for mapped in file.mapped_files() {
+        println!(
+            "Mapped {:?} from {} @ {:?}",
+            mapped.start..mapped.end,
+            mapped.path,
+            mapped.offset
+        );
+    }
+}
+
+fn cmd_readelf(args: ReadelfArgs) -> Result<(), Box<dyn Error>> {
+    let file = read_file(&args.file)?;
+    if args.json {
+        return print_json(&serde_json::to_value(&file)?);
+    }
+    print!("{}", file.readelf());
+    Ok(())
+}
 
-        let mut protection = Protection::NONE;
-        for flag in ph.flags.iter() {
-            protection |= match flag {
-                delf::SegmentFlag::Read => Protection::READ,
-                delf::SegmentFlag::Execute => Protection::EXECUTE,
-                delf::SegmentFlag::Write => Protection::WRITE,
+fn cmd_diff(args: DiffArgs) -> Result<(), Box<dyn Error>> {
+    let old = read_file(&args.old)?;
+    let new = read_file(&args.new)?;
+    let diff = old.diff(&new);
+    if args.json {
+        return print_json(&serde_json::to_value(&diff)?);
+    }
+    print!("{}", diff);
+    Ok(())
+}
+
+fn cmd_checksec(args: ChecksecArgs) -> Result<(), Box<dyn Error>> {
+    let mut reports = Vec::new();
+    for path in &args.files {
+        let hardening = read_file(path)?.hardening();
+        if args.json {
+            let mut report = serde_json::to_value(&hardening)?;
+            report["file"] = json!(path);
+            reports.push(report);
+        } else {
+            if args.files.len() > 1 {
+                println!("{}:", path.display());
             }
+            print!("{}", hardening);
         }
-        unsafe {
-            protect(addr, len, protection)?;
-        }
-        mappings.push(map)
     }
+    if args.json {
+        return print_json(&Value::from(reports));
+    }
+    Ok(())
+}
 
-    println!("Executing {:?} in memory...", input_path);
+fn cmd_symbols(args: SymbolsArgs) -> Result<(), Box<dyn Error>> {
+    let file = read_file(&args.file)?;
+    let syms = if args.dynamic {
+        file.dynamic_symbols().collect::<Vec<_>>()
+    } else {
+        file.symbols().collect()
+    };
 
-    unsafe {
-        jmp(file.entry_point.0 as _);
+    if args.json {
+        let syms = syms
+            .iter()
+            .map(|sym| {
+                json!({
+                    "name": sym.name,
+                    "value": sym.value.0,
+                    "size": sym.size,
+                    "type": format!("{:?}", sym.r#type),
+                    "bind": format!("{:?}", sym.bind),
+                    "visibility": format!("{:?}", sym.visibility),
+                    "shndx": format!("{:?}", sym.shndx),
+                })
+            })
+            .collect::<Vec<_>>();
+        return print_json(&Value::from(syms));
+    }
+
+    for sym in syms {
+        println!("{:?}", sym);
     }
     Ok(())
 }
 
-fn ndisasm(code: &[u8], origin: delf::Addr) -> Result<(), Box<dyn Error>> {
-    use std::{
-        io::Write,
-        process::{Command, Stdio},
+fn cmd_deps(args: DepsArgs) -> Result<(), Box<dyn Error>> {
+    let mut process = process::Process::new();
+    if let Some(dirs) = &args.library_path {
+        process.library_path = env::split_paths(dirs).collect();
+    }
+    let deps = process.dependencies(&args.file)?;
+
+    if args.json {
+        let deps = deps
+            .iter()
+            .map(|dep| {
+                json!({
+                    "name": dep.name,
+                    "needed_by": dep.needed_by,
+                    "path": dep.path,
+                })
+            })
+            .collect::<Vec<_>>();
+        return print_json(&Value::from(deps));
+    }
+
+    for dep in deps {
+        match dep.path {
+            Some(path) => println!(
+                "{} => {} (needed by {})",
+                dep.name,
+                path.display(),
+                dep.needed_by.display()
+            ),
+            None => println!(
+                "{} => not found (needed by {})",
+                dep.name,
+                dep.needed_by.display()
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn cmd_disasm(args: DisasmArgs) -> Result<(), Box<dyn Error>> {
+    let file = read_file(&args.file)?;
+
+    // where code starts, and its contents
+    let code = match (&args.symbol, &args.range) {
+        (Some(name), _) => {
+            let sym = file
+                .symbols()
+                .chain(file.dynamic_symbols())
+                .find(|sym| &sym.name == name && !sym.shndx.is_undef())
+                .ok_or_else(|| format!("symbol {:?} not found", name))?;
+            // hand-written assembly often leaves out sizes: go on until the
+            // end of the section
+            let end = match sym.size {
+                0 => file
+                    .section_headers
+                    .iter()
+                    .filter(|sh| sh.flags.contains(delf::SectionFlag::Alloc))
+                    .find(|sh| sh.mem_range().contains(&sym.value))
+                    .map(|sh| sh.mem_range().end)
+                    .ok_or_else(|| format!("symbol {:?} is in no section", name))?,
+                size => sym.value + delf::Addr(size),
+            };
+            vec![code_at(&file, sym.value..end)?]
+        }
+        (None, Some(range)) => vec![code_at(&file, range.clone())?],
+        (None, None) => {
+            let sections = file
+                .section_headers
+                .iter()
+                .filter(|sh| sh.flags.contains(delf::SectionFlag::ExecInstr))
+                .map(|sh| (sh.addr, &sh.data[..]))
+                .collect::<Vec<_>>();
+            if sections.is_empty() {
+                // stripped of section headers: fall back to segments
+                file.program_headers
+                    .iter()
+                    .filter(|ph| {
+                        ph.r#type == delf::SegmentType::Load
+                            && ph.flags.contains(delf::SegmentFlag::Execute)
+                    })
+                    .map(|ph| (ph.vaddr, &ph.data[..]))
+                    .collect()
+            } else {
+                sections
+            }
+        }
     };
 
-    let mut child = Command::new("ndisasm")
-        .arg("-b")
-        .arg("64")
-        .arg("-o")
-        .arg(format!("{}", origin.0))
-        .arg("-")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()?;
+    let symbols = file.symbol_map();
+    // line numbers are only a bonus: disassemble without them rather than
+    // fail on a `.debug_line` delf cannot read
+    let lines = file.line_table().unwrap_or_default();
+    let mut instructions = Vec::new();
+    for (origin, code) in code {
+        instructions.extend(disasm::disassemble(&file, &symbols, code, origin)?);
+    }
 
-    child.stdin.as_mut().unwrap().write_all(code)?;
-    let output = child.wait_with_output()?;
-    println!("{}", String::from_utf8_lossy(&output.stdout));
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|x| format!("{:02x}", x))
+            .collect::<String>()
+    };
+    if args.json {
+        let instructions = instructions
+            .iter()
+            .map(|ins| {
+                json!({
+                    "addr": ins.addr.0,
+                    "bytes": hex(&ins.bytes),
+                    "text": ins.text,
+                    "label": ins.label,
+                    "line": lines.lookup(ins.addr).map(|(path, line)| {
+                        json!({ "file": path, "line": line })
+                    }),
+                })
+            })
+            .collect::<Vec<_>>();
+        return print_json(&Value::from(instructions));
+    }
 
+    // source lines go above their first instruction, as in `objdump -l`
+    let mut last_line = None;
+    for ins in instructions {
+        if let Some(label) = &ins.label {
+            println!("\n{:?} <{}>:", ins.addr, label);
+        }
+        let line = lines.lookup(ins.addr);
+        if let Some((path, n)) = line.filter(|_| line != last_line) {
+            println!("{}:{}", path.display(), n);
+        }
+        last_line = line;
+        println!("{:?}  {:<20}  {}", ins.addr, hex(&ins.bytes), ins.text);
+    }
     Ok(())
 }
 
-unsafe fn jmp(addr: *const u8) {
-    let fn_ptr: fn() = std::mem::transmute(addr);
-    fn_ptr();
+/// Returns the contents of the file mapped at `range`
+fn code_at(file: &delf::File, range: Range<delf::Addr>) -> Result<(delf::Addr, &[u8]), String> {
+    let len = range.end.0.checked_sub(range.start.0);
+    file.slice_at(range.start)
+        .zip(len)
+        .and_then(|(code, len)| code.get(..len as usize))
+        .map(|code| (range.start, code))
+        .ok_or_else(|| format!("{:?} is not in the file", range))
+}
+
+fn cmd_run(mut args: RunArgs) -> Result<(), Box<dyn Error>> {
+    if args.sandbox {
+        args.sandbox = false;
+        let status = sandbox::run(|| cmd_run(args))?;
+        std::process::exit(status);
+    }
+
+    let file = read_file(&args.file)?;
+    if (file.machine, file.class) != (delf::Machine::X86_64, delf::Class::Elf64) {
+        return Err(format!(
+            "cannot execute {:?} {:?} binaries, only x86-64 ones",
+            file.class, file.machine
+        )
+        .into());
+    }
+
+    let mut process = process::Process::new();
+    if let Some(dirs) = &args.library_path {
+        process.library_path = env::split_paths(dirs).collect();
+    }
+    process.bind_now = args.bind_now || env::var_os("LD_BIND_NOW").is_some_and(|x| !x.is_empty());
+    process.trace_bindings = args.trace_bindings;
+    let index = process.load_object_and_dependencies(&args.file, args.base)?;
+    if args.verbose {
+        for object in &process.objects {
+            eprintln!("Loaded {:?}", object);
+        }
+    }
+
+    process.adjust_protections()?;
+    process.apply_relocations()?;
+    process.stand_in_for_rtld();
+    process.protect_relocated()?;
+    let tls = tls::StaticTls::new(&process)?;
+    let ctype_init = init::ctype_init(&process);
+    let initializers = init::initializers(&process, index);
+    init::set_finalizers(init::finalizers(&process, index));
+    // functions bound lazily are resolved once the program runs, which
+    // needs the process to stay around
+    let process: &'static process::Process = Box::leak(Box::new(process));
+    lazy::install(process);
+    if !args.breaks.is_empty() {
+        debugger::install(process, &args.breaks)?;
+    }
+
+    // the program gets the rest of the arguments, and elk's environment
+    let program_args = std::iter::once(args.file.clone().into_os_string())
+        .chain(args.args.into_iter().map(OsString::from))
+        .collect::<Vec<_>>();
+    let program_env = env::vars_os()
+        .map(|(key, value)| {
+            let mut var = key;
+            var.push("=");
+            var.push(value);
+            var
+        })
+        .collect::<Vec<_>>();
+    let stack = stack::InitialStack::new(&program_args, &program_env, &process.auxv(index)?)?;
+
+    let object = &process.objects[index];
+    let entry_point = object.base + object.file.entry_point;
+    if args.verbose {
+        eprintln!("Executing {:?} at {:?}", args.file, entry_point);
+    }
+
+    unsafe {
+        // the program's C library grows the heap with `brk` too, and neither
+        // knows about the other moving the break: elk's own allocations,
+        // from lazy binding or the debugger, must come from `mmap` from now on
+        libc::mallopt(libc::M_MMAP_THRESHOLD, 0);
+        libc::mallopt(libc::M_TRIM_THRESHOLD, libc::c_int::MAX);
+        sandbox::catch_crashes()?;
+        if args.seccomp {
+            sandbox::restrict()?;
+        }
+        tls.install()?;
+        if let Some(ctype_init) = ctype_init {
+            init::run_ctype_init(ctype_init);
+        }
+        init::run_initializers(&initializers, stack.pointer);
+        jmp(entry_point.0, stack.pointer)
+    }
+}
+
+/// Switches to the program's stack and jumps to its entry point, never to
+/// return. `rdx` holds a function for the program to register with
+/// `atexit`, which runs the finalizers.
+unsafe fn jmp(entry_point: u64, stack_pointer: u64) -> ! {
+    std::arch::asm!(
+        "mov rsp, {stack_pointer}",
+        "jmp {entry_point}",
+        entry_point = in(reg) entry_point,
+        stack_pointer = in(reg) stack_pointer,
+        in("rdx") init::run_finalizers as *const (),
+        options(noreturn)
+    )
 }
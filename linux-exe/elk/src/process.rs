@@ -0,0 +1,957 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt, fs, io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use delf::{Addr, AuxType, AuxvEntry, DynamicTag, RelType, SegmentType, Sym, SymBind, SymType};
+use mmap::{MapOption, MemoryMap};
+use region::Protection;
+
+use crate::{lazy, tls};
+
+/// Searched last, after the directories an object asks for
+const DEFAULT_SEARCH_PATH: &[&str] = &[
+    "/lib/x86_64-linux-gnu",
+    "/usr/lib/x86_64-linux-gnu",
+    "/lib64",
+    "/usr/lib64",
+    "/lib",
+    "/usr/lib",
+];
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, delf::Error),
+    /// No file by that name in any of the searched directories
+    NotFound(String, Vec<PathBuf>),
+    NoLoadSegments(PathBuf),
+    /// Something is already mapped in the range the object must go to
+    AddressInUse(PathBuf, Range<Addr>),
+    /// The requested base does not honour the alignment of the segments
+    MisalignedBase(PathBuf, Addr, u64),
+    /// A segment has more bytes in the file than in memory
+    SegmentTooLarge(PathBuf, Addr),
+    /// The object's address range does not fit at the place it was given
+    NoRoom(PathBuf, Range<Addr>),
+    Map(PathBuf, mmap::MapError),
+    Protect(PathBuf, region::Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            Self::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            Self::NotFound(name, dirs) => {
+                write!(f, "library {:?} not found, searched in {:?}", name, dirs)
+            }
+            Self::NoLoadSegments(path) => write!(f, "{}: no Load segments", path.display()),
+            Self::AddressInUse(path, range) => write!(
+                f,
+                "{}: address range {:?} is already in use",
+                path.display(),
+                range
+            ),
+            Self::MisalignedBase(path, base, align) => write!(
+                f,
+                "{}: base {:?} is not aligned to {:#x} bytes",
+                path.display(),
+                base,
+                align
+            ),
+            Self::SegmentTooLarge(path, vaddr) => write!(
+                f,
+                "{}: segment at {:?} has more bytes in the file than in memory",
+                path.display(),
+                vaddr
+            ),
+            Self::NoRoom(path, range) => write!(
+                f,
+                "{}: address range {:?} does not fit where it was mapped",
+                path.display(),
+                range
+            ),
+            Self::Map(path, e) => write!(f, "{}: could not map object: {}", path.display(), e),
+            Self::Protect(path, e) => {
+                write!(f, "{}: could not protect segment: {}", path.display(), e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+#[derive(Debug)]
+pub enum RelocationError {
+    Rela(PathBuf, delf::Error),
+    Unimplemented(PathBuf, RelType),
+    UnknownSymbolNumber(PathBuf, u32),
+    UndefinedSymbol(PathBuf, String),
+    /// A TLS relocation refers to a symbol of an object without TLS
+    NoTls(PathBuf, String),
+}
+
+impl fmt::Display for RelocationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Rela(path, e) => write!(f, "{}: {}", path.display(), e),
+            Self::Unimplemented(path, r#type) => {
+                write!(
+                    f,
+                    "{}: unimplemented relocation {:?}",
+                    path.display(),
+                    r#type
+                )
+            }
+            Self::UnknownSymbolNumber(path, n) => {
+                write!(f, "{}: unknown symbol number {}", path.display(), n)
+            }
+            Self::UndefinedSymbol(path, name) => {
+                write!(f, "{}: undefined symbol {:?}", path.display(), name)
+            }
+            Self::NoTls(path, name) => write!(
+                f,
+                "{}: thread-local symbol {:?} is in an object without TLS",
+                path.display(),
+                name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RelocationError {}
+
+pub struct Object {
+    pub path: PathBuf,
+    /// What addresses in the file are relative to: zero for non-PIE
+    /// executables, wherever the object was mapped otherwise
+    pub base: Addr,
+    pub file: delf::File,
+    /// Addresses spanned by the `Load` segments, page-aligned and before
+    /// adding `base`
+    pub mem_range: Range<Addr>,
+    /// Covers `mem_range` and possibly some slack around it: the gaps are
+    /// made inaccessible by `adjust_protections`
+    pub map: MemoryMap,
+    /// Dynamic symbols, indexed like relocations refer to them
+    pub syms: Vec<Sym>,
+    /// Where the object's TLS block is below the thread pointer, if it has
+    /// one
+    pub tls_offset: Option<u64>,
+    /// Indices of the objects its `DT_NEEDED` entries refer to
+    pub dependencies: Vec<usize>,
+}
+
+impl fmt::Debug for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mem_range = self.base + self.mem_range.start..self.base + self.mem_range.end;
+        write!(f, "{} @ {:?}", self.path.display(), mem_range)
+    }
+}
+
+impl Object {
+    fn load_segments(&self) -> impl Iterator<Item = &delf::ProgramHeader> {
+        self.file
+            .program_headers
+            .iter()
+            .filter(|ph| ph.r#type == SegmentType::Load && ph.memsz != Addr(0))
+    }
+
+    /// Page-aligned ranges covered by the `Load` segments, before adding
+    /// `base`, with the protection their flags ask for. Segments sharing a
+    /// page are merged, with the union of their protections.
+    fn mapped_ranges(&self) -> Vec<(Range<Addr>, Protection)> {
+        let page_size = region::page::size() as u64;
+        let mut ranges = self
+            .load_segments()
+            .map(|ph| {
+                let range = ph.mem_range();
+                let range = Addr(align_down(range.start.0, page_size))
+                    ..Addr(align_up(range.end.0, page_size));
+                let mut protection = Protection::NONE;
+                for flag in ph.flags.iter() {
+                    protection |= match flag {
+                        delf::SegmentFlag::Read => Protection::READ,
+                        delf::SegmentFlag::Write => Protection::WRITE,
+                        delf::SegmentFlag::Execute => Protection::EXECUTE,
+                    }
+                }
+                (range, protection)
+            })
+            .collect::<Vec<_>>();
+        ranges.sort_by_key(|(range, _)| range.start);
+
+        let mut res: Vec<(Range<Addr>, Protection)> = Vec::new();
+        for (range, protection) in ranges {
+            match res.last_mut() {
+                Some((last, last_protection)) if range.start < last.end => {
+                    last.end = last.end.max(range.end);
+                    *last_protection |= protection;
+                }
+                _ => res.push((range, protection)),
+            }
+        }
+        res
+    }
+
+    /// Where the program headers are in memory, for `AT_PHDR`
+    pub fn program_headers_addr(&self) -> Option<Addr> {
+        if let Some(ph) = self.file.segment_of_type(SegmentType::Phdr) {
+            return Some(self.base + ph.vaddr);
+        }
+        let offset = self.file.ph_offset;
+        self.load_segments()
+            .find(|ph| ph.file_range().contains(&offset))
+            .map(|ph| self.base + ph.vaddr + (offset - ph.offset))
+    }
+}
+
+/// A library needed by an object, and where it was found
+pub struct Dependency {
+    pub name: String,
+    pub needed_by: PathBuf,
+    /// `None` if it is in none of the searched directories
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Default)]
+pub struct Process {
+    /// In load order: the executable first, then its dependencies, breadth
+    /// first
+    pub objects: Vec<Object>,
+    pub objects_by_path: HashMap<PathBuf, usize>,
+    /// Searched for libraries before `DT_RUNPATH`, like `LD_LIBRARY_PATH`
+    pub library_path: Vec<PathBuf>,
+    /// TLS blocks of the objects loaded so far
+    pub tls: tls::Layout,
+    /// Resolve `JUMP_SLOT` relocations at load time for all objects, rather
+    /// than on first call, like `LD_BIND_NOW`
+    pub bind_now: bool,
+    /// Log every symbol resolution to stderr
+    pub trace_bindings: bool,
+}
+
+pub fn align_down(x: u64, align: u64) -> u64 {
+    x & !(align - 1)
+}
+
+pub fn align_up(x: u64, align: u64) -> u64 {
+    align_down(x + align - 1, align)
+}
+
+impl Process {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads an object and, recursively, the libraries it needs. Returns
+    /// the index of the object.
+    pub fn load_object_and_dependencies(
+        &mut self,
+        path: &Path,
+        base: Option<Addr>,
+    ) -> Result<usize, LoadError> {
+        let index = self.load_object(path, base)?;
+
+        let mut queue = VecDeque::from([index]);
+        while let Some(index) = queue.pop_front() {
+            let object = &self.objects[index];
+            let search_path = self.search_path(&object.path, &object.file);
+            let mut dependencies = Vec::new();
+            for name in object.file.needed_libraries() {
+                let path = Self::find_library(&name, &search_path)?;
+                let path = path.canonicalize().map_err(|e| LoadError::Io(path, e))?;
+                let dependency = match self.objects_by_path.get(&path) {
+                    Some(&dependency) => dependency,
+                    None => {
+                        let dependency = self.load_object(&path, None)?;
+                        queue.push_back(dependency);
+                        dependency
+                    }
+                };
+                dependencies.push(dependency);
+            }
+            self.objects[index].dependencies = dependencies;
+        }
+        Ok(index)
+    }
+
+    /// Finds the libraries an object needs, recursively and in the same
+    /// order as `load_object_and_dependencies`, without loading anything.
+    /// Libraries needed by several objects are listed each time.
+    pub fn dependencies(&self, path: &Path) -> Result<Vec<Dependency>, LoadError> {
+        let path = path
+            .canonicalize()
+            .map_err(|e| LoadError::Io(path.to_owned(), e))?;
+        let file = read_file(&path)?;
+
+        let mut res = Vec::new();
+        let mut seen = HashSet::from([path.clone()]);
+        let mut queue = VecDeque::from([(path, file)]);
+        while let Some((path, file)) = queue.pop_front() {
+            let search_path = self.search_path(&path, &file);
+            for name in file.needed_libraries() {
+                let found = match Self::find_library(&name, &search_path) {
+                    Ok(found) => Some(found.canonicalize().map_err(|e| LoadError::Io(found, e))?),
+                    Err(_) => None,
+                };
+                if let Some(found) = &found {
+                    if seen.insert(found.clone()) {
+                        queue.push_back((found.clone(), read_file(found)?));
+                    }
+                }
+                res.push(Dependency {
+                    name,
+                    needed_by: path.clone(),
+                    path: found,
+                });
+            }
+        }
+        Ok(res)
+    }
+
+    /// Where to look for the libraries an object needs, in the same order
+    /// as glibc: `DT_RPATH` (unless there is a `DT_RUNPATH`), the library
+    /// path, `DT_RUNPATH` and finally the default directories.
+    fn search_path(&self, path: &Path, file: &delf::File) -> Vec<PathBuf> {
+        let origin = path.parent().unwrap_or_else(|| Path::new("/"));
+        let origin = origin.to_string_lossy();
+        let expand = |dirs: String| {
+            dirs.split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(|dir| {
+                    let dir = dir.replace("${ORIGIN}", &origin);
+                    PathBuf::from(dir.replace("$ORIGIN", &origin))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let runpath = file.runpath();
+        let mut res = Vec::new();
+        if runpath.is_none() {
+            res.extend(file.rpath().map(expand).unwrap_or_default());
+        }
+        res.extend(self.library_path.iter().cloned());
+        res.extend(runpath.map(expand).unwrap_or_default());
+        res.extend(DEFAULT_SEARCH_PATH.iter().map(PathBuf::from));
+        res
+    }
+
+    fn find_library(name: &str, search_path: &[PathBuf]) -> Result<PathBuf, LoadError> {
+        // names with a slash are paths, and are not searched for
+        if name.contains('/') {
+            return Ok(PathBuf::from(name));
+        }
+        search_path
+            .iter()
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| LoadError::NotFound(name.to_owned(), search_path.to_vec()))
+    }
+
+    /// Maps the `Load` segments of an object, writable until
+    /// `adjust_protections` is called.
+    ///
+    /// Non-PIE executables go at the addresses in their program headers.
+    /// Other objects go at `base` if given, or wherever the kernel finds
+    /// room otherwise.
+    pub fn load_object(&mut self, path: &Path, base: Option<Addr>) -> Result<usize, LoadError> {
+        let path = path
+            .canonicalize()
+            .map_err(|e| LoadError::Io(path.to_owned(), e))?;
+        let file = read_file(&path)?;
+
+        let page_size = region::page::size() as u64;
+        let load_segments = || {
+            // linkers sometimes emit empty ones, which need no mapping
+            file.program_headers
+                .iter()
+                .filter(|ph| ph.r#type == SegmentType::Load && ph.memsz != Addr(0))
+        };
+        let mem_range = load_segments()
+            .map(|ph| ph.mem_range())
+            .reduce(|a, b| a.start.min(b.start)..a.end.max(b.end))
+            .ok_or_else(|| LoadError::NoLoadSegments(path.clone()))?;
+        if let Some(ph) = load_segments().find(|ph| ph.data.len() as u64 > ph.memsz.0) {
+            return Err(LoadError::SegmentTooLarge(path, ph.vaddr));
+        }
+        let mem_range = Addr(align_down(mem_range.start.0, page_size))
+            ..Addr(align_up(mem_range.end.0, page_size));
+        let len = u64::from(mem_range.end - mem_range.start);
+        // segments are relocated together, so the strictest alignment
+        // applies to the whole object
+        let align = load_segments()
+            .map(|ph| ph.align.0)
+            .filter(|align| align.is_power_of_two())
+            .fold(page_size, u64::max);
+
+        let (map, base) = match (file.r#type, base) {
+            (delf::Type::Exec, _) => (map_fixed(&path, mem_range.clone())?, Addr(0)),
+            (_, Some(base)) => {
+                if base.0 % align != 0 {
+                    return Err(LoadError::MisalignedBase(path, base, align));
+                }
+                let range = match (
+                    base.0.checked_add(mem_range.start.0),
+                    base.0.checked_add(mem_range.end.0),
+                ) {
+                    (Some(start), Some(end)) => Addr(start)..Addr(end),
+                    _ => return Err(LoadError::NoRoom(path, mem_range)),
+                };
+                (map_fixed(&path, range)?, base)
+            }
+            (_, None) => {
+                // map enough to be able to pick an aligned start, and let
+                // the kernel find room for it
+                let map = MemoryMap::new(
+                    (len + align - page_size) as usize,
+                    &[MapOption::MapReadable, MapOption::MapWritable],
+                )
+                .map_err(|e| LoadError::Map(path.clone(), e))?;
+                let start = align_up(map.data() as u64, align);
+                // the kernel may well pick an address below the one the
+                // object was linked at
+                let base = start
+                    .checked_sub(mem_range.start.0)
+                    .ok_or_else(|| LoadError::NoRoom(path.clone(), mem_range.clone()))?;
+                (map, Addr(base))
+            }
+        };
+
+        for ph in load_segments() {
+            let start = (base + ph.vaddr).0 as *mut u8;
+            unsafe {
+                std::ptr::copy_nonoverlapping(ph.data.as_ptr(), start, ph.data.len());
+                // the rest of the segment is not in the file (.bss): the
+                // mapping starts out zeroed, but a previous segment sharing
+                // the page may have written there
+                std::ptr::write_bytes(
+                    start.add(ph.data.len()),
+                    0,
+                    usize::from(ph.memsz) - ph.data.len(),
+                );
+            }
+        }
+
+        let syms = file.dynamic_symbols().collect();
+        let tls_offset = file
+            .segment_of_type(SegmentType::Tls)
+            .map(|ph| self.tls.add(ph));
+        let object = Object {
+            path: path.clone(),
+            base,
+            file,
+            mem_range,
+            map,
+            syms,
+            tls_offset,
+            dependencies: Vec::new(),
+        };
+
+        let index = self.objects.len();
+        self.objects.push(object);
+        self.objects_by_path.insert(path, index);
+        Ok(index)
+    }
+
+    /// Finds the definition of a global symbol, in load order, in objects
+    /// other than `skip`. Returns the index of the object defining it.
+    fn lookup_symbol(&self, name: &str, skip: Option<usize>) -> Option<(usize, &Sym)> {
+        self.objects.iter().enumerate().find_map(|(index, object)| {
+            if Some(index) == skip {
+                return None;
+            }
+            object
+                .syms
+                .iter()
+                .find(|sym| {
+                    sym.name == name
+                        && !sym.shndx.is_undef()
+                        && matches!(sym.bind, SymBind::Global | SymBind::Weak)
+                })
+                .map(|sym| (index, sym))
+        })
+    }
+
+    /// Address of a global symbol, from the first object defining it
+    pub fn symbol_addr(&self, name: &str) -> Option<Addr> {
+        self.lookup_symbol(name, None)
+            .map(|(index, sym)| self.objects[index].base + sym.value)
+    }
+
+    /// Definition of the symbol a relocation of the object at `index` refers
+    /// to, along with the index of the object defining it. Undefined weak
+    /// symbols have none, and so does symbol 0, which relocations refer to
+    /// when they need no symbol.
+    fn find_symbol(&self, index: usize, n: u32) -> Result<Option<(usize, &Sym)>, RelocationError> {
+        if n == 0 {
+            return Ok(None);
+        }
+        let object = &self.objects[index];
+        let sym = object
+            .syms
+            .get(n as usize)
+            .ok_or_else(|| RelocationError::UnknownSymbolNumber(object.path.clone(), n))?;
+        if sym.bind == SymBind::Local {
+            return Ok(Some((index, sym)));
+        }
+        match self.lookup_symbol(&sym.name, None) {
+            Some(found) => Ok(Some(found)),
+            None if sym.bind == SymBind::Weak => Ok(None),
+            None => Err(RelocationError::UndefinedSymbol(
+                object.path.clone(),
+                sym.name.clone(),
+            )),
+        }
+    }
+
+    /// Value of the symbol a relocation of the object at `index` refers to
+    fn resolve_symbol(&self, index: usize, n: u32) -> Result<Addr, RelocationError> {
+        let (res, provider) = match self.find_symbol(index, n) {
+            // the value of an indirect function is its resolver, which
+            // returns the implementation to use
+            Ok(Some((definer, sym))) if sym.r#type == SymType::GnuIFunc => {
+                let definer = &self.objects[definer];
+                let value = unsafe { call_resolver(definer.base + sym.value) };
+                (value, definer.path.to_string_lossy())
+            }
+            Ok(Some((definer, sym))) => {
+                let definer = &self.objects[definer];
+                (definer.base + sym.value, definer.path.to_string_lossy())
+            }
+            // unresolved weak symbols are null, and so is no symbol
+            Ok(None) => (Addr(0), "nowhere".into()),
+            // normally provided by the dynamic linker, which elk stands in
+            // for
+            Err(RelocationError::UndefinedSymbol(_, name)) if name == "__tls_get_addr" => {
+                (Addr(tls::tls_get_addr as *const () as u64), "elk".into())
+            }
+            Err(e) => return Err(e),
+        };
+        if self.trace_bindings {
+            let object = &self.objects[index];
+            eprintln!(
+                "[bind] {}: {:?} => {:?} in {}",
+                object.path.display(),
+                object.syms[n as usize].name,
+                res,
+                provider
+            );
+        }
+        Ok(res)
+    }
+
+    /// Whether `JUMP_SLOT` relocations of an object are left for
+    /// `bind_lazily`. This needs the PLT header to find the resolver in the
+    /// GOT.
+    fn binds_lazily(&self, object: &Object) -> bool {
+        !self.bind_now
+            && !object.file.bind_now()
+            && object.file.dynamic_entry(DynamicTag::PltGot).is_some()
+    }
+
+    /// Resolves the `JUMP_SLOT` relocation at index `rela` of the `DT_JMPREL`
+    /// table of the object at `index`, on first call through the PLT.
+    /// Returns the address of the function.
+    pub fn bind_lazily(&self, index: usize, rela: usize) -> Result<Addr, RelocationError> {
+        let object = &self.objects[index];
+        let relas = object
+            .file
+            .plt_rela_entries()
+            .map_err(|e| RelocationError::Rela(object.path.clone(), e))?;
+        let rela = relas.get(rela).ok_or_else(|| {
+            RelocationError::Rela(
+                object.path.clone(),
+                delf::Error::InvalidRela(Addr(rela as u64)),
+            )
+        })?;
+        let value = self.resolve_symbol(index, rela.sym)?;
+        let target = (object.base + rela.offset).0 as *mut u64;
+        unsafe {
+            target.write_unaligned(value.0);
+        }
+        Ok(value)
+    }
+
+    /// Object defining the thread-local symbol a relocation of the object at
+    /// `index` refers to, along with the symbol's offset in its TLS block.
+    /// With no symbol, the relocation is about the object's own block, and
+    /// the addend holds the offset.
+    fn resolve_tls_symbol(&self, index: usize, n: u32) -> Result<(&Object, Addr), RelocationError> {
+        if n == 0 {
+            return Ok((&self.objects[index], Addr(0)));
+        }
+        let (definer, sym) = self.find_symbol(index, n)?.ok_or_else(|| {
+            let name = self.objects[index].syms[n as usize].name.clone();
+            RelocationError::UndefinedSymbol(self.objects[index].path.clone(), name)
+        })?;
+        Ok((&self.objects[definer], sym.value))
+    }
+
+    /// Applies the relocations of all objects. This runs code from them, the
+    /// resolvers of indirect functions, so it must come after
+    /// `adjust_protections`.
+    pub fn apply_relocations(&self) -> Result<(), RelocationError> {
+        // dependencies first, like glibc: indirect function resolvers and
+        // `COPY` relocations read data that must be relocated already
+        for (index, object) in self.objects.iter().enumerate().rev() {
+            let rela_err = |e| RelocationError::Rela(object.path.clone(), e);
+            let mut relas = object.file.rela_entries().map_err(rela_err)?;
+            relas.extend(object.file.plt_rela_entries().map_err(rela_err)?);
+
+            let lazy = self.binds_lazily(object);
+            if let (true, Some(got)) = (lazy, object.file.dynamic_entry(DynamicTag::PltGot)) {
+                // read by the PLT header: GOT[1] identifies the object,
+                // GOT[2] is the resolver
+                let got = (object.base + got).0 as *mut u64;
+                unsafe {
+                    got.add(1).write_unaligned(index as u64);
+                    got.add(2)
+                        .write_unaligned(lazy::trampoline as *const () as u64);
+                }
+            }
+
+            for addr in object.file.relr_entries().map_err(rela_err)? {
+                let target = (object.base + addr).0 as *mut u64;
+                unsafe {
+                    target.write_unaligned(target.read_unaligned().wrapping_add(object.base.0));
+                }
+            }
+
+            for rela in relas {
+                let value = match rela.r#type {
+                    RelType::None => continue,
+                    RelType::Relative => object.base + rela.addend,
+                    RelType::IRelative => unsafe { call_resolver(object.base + rela.addend) },
+                    // the executable has its own copy of a variable defined
+                    // by a library, initialized from the library's
+                    RelType::Copy => {
+                        let sym = object.syms.get(rela.sym as usize).ok_or_else(|| {
+                            RelocationError::UnknownSymbolNumber(object.path.clone(), rela.sym)
+                        })?;
+                        let (definer, def) =
+                            self.lookup_symbol(&sym.name, Some(index)).ok_or_else(|| {
+                                RelocationError::UndefinedSymbol(
+                                    object.path.clone(),
+                                    sym.name.clone(),
+                                )
+                            })?;
+                        let src = (self.objects[definer].base + def.value).0 as *const u8;
+                        let dst = (object.base + rela.offset).0 as *mut u8;
+                        unsafe {
+                            std::ptr::copy_nonoverlapping(
+                                src,
+                                dst,
+                                sym.size.min(def.size) as usize,
+                            );
+                        }
+                        continue;
+                    }
+                    RelType::_64 => {
+                        let sym = self.resolve_symbol(index, rela.sym)?;
+                        Addr(sym.0.wrapping_add(rela.addend.0))
+                    }
+                    // the GOT entry points into the PLT entry, past the jump
+                    // to the GOT: only relocate it
+                    RelType::JumpSlot if lazy => {
+                        let target = (object.base + rela.offset).0 as *const u64;
+                        object.base + Addr(unsafe { target.read_unaligned() })
+                    }
+                    RelType::GlobDat | RelType::JumpSlot => self.resolve_symbol(index, rela.sym)?,
+                    // offset from the thread pointer, for the initial exec
+                    // model
+                    RelType::TpOff64 => {
+                        let (definer, offset) = self.resolve_tls_symbol(index, rela.sym)?;
+                        let block = definer.tls_offset.ok_or_else(|| {
+                            RelocationError::NoTls(
+                                definer.path.clone(),
+                                object.syms[rela.sym as usize].name.clone(),
+                            )
+                        })?;
+                        Addr((offset.0 + rela.addend.0).wrapping_sub(block))
+                    }
+                    // module ID and offset in the module's block, for
+                    // `__tls_get_addr`
+                    // `tls_get_addr` relies on the module having a block
+                    RelType::DtpMod64 => {
+                        let (definer, _) = self.resolve_tls_symbol(index, rela.sym)?;
+                        if definer.tls_offset.is_none() {
+                            return Err(RelocationError::NoTls(
+                                definer.path.clone(),
+                                object.syms[rela.sym as usize].name.clone(),
+                            ));
+                        }
+                        let module = self.objects_by_path[&definer.path] + 1;
+                        Addr(module as u64)
+                    }
+                    RelType::DtpOff64 => {
+                        let (_, offset) = self.resolve_tls_symbol(index, rela.sym)?;
+                        offset + rela.addend
+                    }
+                    r#type => {
+                        return Err(RelocationError::Unimplemented(object.path.clone(), r#type))
+                    }
+                };
+                let target = (object.base + rela.offset).0 as *mut u64;
+                unsafe {
+                    target.write_unaligned(value.0);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills in what glibc's C library expects its dynamic linker to have
+    /// set up in `_rtld_global`, if it is loaded. The list of loaded objects
+    /// gets a single, empty entry: `__libc_start_main` looks for the
+    /// program's initializers there, which elk runs itself.
+    pub fn stand_in_for_rtld(&self) {
+        let Some(rtld_global) = self.symbol_addr("_rtld_global") else {
+            return;
+        };
+        // a `struct link_map`, whose layout changes with each glibc release:
+        // zeroes mean no dynamic section, no name and no next object
+        let link_map: &'static mut [u64] = Box::leak(vec![0; 512].into_boxed_slice());
+        unsafe {
+            // `_dl_ns[0]._ns_loaded`, the first field
+            (rtld_global.0 as *mut u64).write_unaligned(link_map.as_mut_ptr() as u64);
+        }
+    }
+
+    /// Objects in the order their initializers run: dependencies before the
+    /// objects needing them, depth first from the object at `index`, which
+    /// comes last. Finalizers run in the reverse order.
+    pub fn init_order(&self, index: usize) -> Vec<usize> {
+        fn visit(process: &Process, index: usize, seen: &mut HashSet<usize>, res: &mut Vec<usize>) {
+            if !seen.insert(index) {
+                return;
+            }
+            for &dependency in &process.objects[index].dependencies {
+                visit(process, dependency, seen, res);
+            }
+            res.push(index);
+        }
+
+        let mut res = Vec::new();
+        visit(self, index, &mut HashSet::new(), &mut res);
+        res
+    }
+
+    /// Auxiliary vector for starting the object at `index`. Entries about
+    /// the host (vDSO, hardware capabilities, user ids...) are passed
+    /// through from elk's own, the ones about the program are replaced.
+    pub fn auxv(&self, index: usize) -> Result<Vec<AuxvEntry>, Box<dyn std::error::Error>> {
+        let host = fs::read("/proc/self/auxv")?;
+        let host = AuxvEntry::from_bytes(delf::Class::Elf64, delf::Endianness::Little, &host)?;
+        let mut res = host
+            .into_iter()
+            .filter(|e| {
+                use AuxType::*;
+                matches!(
+                    e.r#type,
+                    SysInfoEhdr
+                        | HwCap
+                        | HwCap2
+                        | ClkTck
+                        | Uid
+                        | EUid
+                        | Gid
+                        | EGid
+                        | Secure
+                        | Platform
+                        | MinSigStkSz
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let object = &self.objects[index];
+        let own = [
+            (
+                AuxType::Phdr,
+                object.program_headers_addr().unwrap_or(Addr(0)).0,
+            ),
+            // size of an ELF64 program header
+            (AuxType::PhEnt, 56),
+            (AuxType::PhNum, object.file.program_headers.len() as u64),
+            (AuxType::PageSz, region::page::size() as u64),
+            // there is no separate interpreter: elk does its job
+            (AuxType::Base, 0),
+            (AuxType::Flags, 0),
+            (AuxType::Entry, (object.base + object.file.entry_point).0),
+        ];
+        res.extend(own.map(|(r#type, value)| AuxvEntry { r#type, value }));
+        Ok(res)
+    }
+
+    /// Gives every segment the protection its flags ask for, except that
+    /// objects with text relocations stay writable until `protect_relocated`
+    /// is called. The rest of the mapping is made inaccessible.
+    pub fn adjust_protections(&self) -> Result<(), LoadError> {
+        for object in &self.objects {
+            let protect = |start: *const u8, len: usize, protection| unsafe {
+                region::protect(start, len, protection)
+                    .map_err(|e| LoadError::Protect(object.path.clone(), e))
+            };
+            protect(object.map.data(), object.map.len(), Protection::NONE)?;
+            let text_relocations = object.file.has_text_relocations();
+            for (range, mut protection) in object.mapped_ranges() {
+                if text_relocations {
+                    protection |= Protection::WRITE;
+                }
+                let start = (object.base + range.start).0 as *const u8;
+                protect(start, (range.end - range.start).into(), protection)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes away the write access relocations needed, once they are
+    /// applied: from the segments of objects with text relocations, and from
+    /// the `GnuRelRo` ranges, which only relocations write to.
+    pub fn protect_relocated(&self) -> Result<(), LoadError> {
+        let page_size = region::page::size() as u64;
+        for object in &self.objects {
+            let protect = |start: Addr, end: Addr, protection| unsafe {
+                region::protect(start.0 as *const u8, (end - start).into(), protection)
+                    .map_err(|e| LoadError::Protect(object.path.clone(), e))
+            };
+            if object.file.has_text_relocations() {
+                for (range, protection) in object.mapped_ranges() {
+                    protect(
+                        object.base + range.start,
+                        object.base + range.end,
+                        protection,
+                    )?;
+                }
+            }
+            // like glibc, leave alone the end of a page only partly covered
+            let relro = object
+                .file
+                .program_headers
+                .iter()
+                .filter(|ph| ph.r#type == SegmentType::GnuRelRo);
+            for ph in relro {
+                let range = ph.mem_range();
+                let start = Addr(align_down((object.base + range.start).0, page_size));
+                let end = Addr(align_down((object.base + range.end).0, page_size));
+                if start < end {
+                    protect(start, end, Protection::READ)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Calls the resolver of an indirect function, which picks an
+/// implementation for the CPU it runs on
+unsafe fn call_resolver(resolver: Addr) -> Addr {
+    let resolver: extern "C" fn() -> u64 = std::mem::transmute(resolver.0);
+    Addr(resolver())
+}
+
+fn read_file(path: &Path) -> Result<delf::File, LoadError> {
+    let input = fs::read(path).map_err(|e| LoadError::Io(path.to_owned(), e))?;
+    delf::File::from_bytes(&input[..]).map_err(|e| LoadError::Parse(path.to_owned(), e))
+}
+
+/// Maps `range` readable and writable, unless anything is already mapped
+/// there
+fn map_fixed(path: &Path, range: Range<Addr>) -> Result<MemoryMap, LoadError> {
+    let map = MemoryMap::new(
+        (range.end - range.start).into(),
+        &[
+            MapOption::MapReadable,
+            MapOption::MapWritable,
+            MapOption::MapAddr(range.start.0 as *const u8),
+            // unlike MAP_FIXED, this fails instead of replacing what is
+            // there already
+            MapOption::MapNonStandardFlags(
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED_NOREPLACE,
+            ),
+        ],
+    );
+    match map {
+        // kernels older than 4.17 ignore the flag, and take the address as
+        // a hint instead
+        Ok(map) if map.data() as u64 == range.start.0 => Ok(map),
+        Ok(_) => Err(LoadError::AddressInUse(path.to_owned(), range)),
+        Err(mmap::MapError::ErrUnknown(code)) if code == libc::EEXIST as isize => {
+            Err(LoadError::AddressInUse(path.to_owned(), range))
+        }
+        Err(e) => Err(LoadError::Map(path.to_owned(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use delf::{DynamicEntry, DynamicTag, SegmentContents, SegmentType};
+
+    use super::{Process, DEFAULT_SEARCH_PATH};
+
+    fn dynamic_table(file: &mut delf::File) -> &mut Vec<DynamicEntry> {
+        let ph = file
+            .program_headers
+            .iter_mut()
+            .find(|ph| ph.r#type == SegmentType::Dynamic)
+            .unwrap();
+        match &mut ph.contents {
+            SegmentContents::Dynamic(entries) => entries,
+            _ => panic!("no dynamic table"),
+        }
+    }
+
+    #[test]
+    fn search_path() {
+        let input = include_bytes!("../samples/greet");
+        let mut file = delf::File::from_bytes(&input[..]).unwrap();
+        let process = Process {
+            library_path: vec![PathBuf::from("/library-path")],
+            ..Process::new()
+        };
+        let path = Path::new("/samples/greet");
+        let expected = |dirs: &[&str]| {
+            dirs.iter()
+                .chain(DEFAULT_SEARCH_PATH)
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        };
+
+        // DT_RUNPATH, of $ORIGIN, comes after the library path
+        assert_eq!(
+            process.search_path(path, &file),
+            expected(&["/library-path", "/samples"])
+        );
+
+        // DT_RPATH is ignored when there is a DT_RUNPATH...
+        let entries = dynamic_table(&mut file);
+        let runpath = entries
+            .iter()
+            .position(|e| e.tag == DynamicTag::RunPath)
+            .unwrap();
+        let origin = entries[runpath].addr;
+        entries.insert(
+            0,
+            DynamicEntry {
+                tag: DynamicTag::RPath,
+                addr: origin,
+            },
+        );
+        assert_eq!(
+            process.search_path(path, &file),
+            expected(&["/library-path", "/samples"])
+        );
+
+        // ...and comes before the library path otherwise
+        dynamic_table(&mut file).remove(runpath + 1);
+        assert_eq!(
+            process.search_path(path, &file),
+            expected(&["/samples", "/library-path"])
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! `run --sandbox`: loading and running the program in a child process, so
+//! that elk outlives it crashing and can tell how it did. `run --seccomp`
+//! also denies the program a few system calls.
+
+use std::{
+    error::Error,
+    fs,
+    io::{self, Read, Write},
+    mem,
+    os::unix::io::FromRawFd,
+    ptr,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+use libc::c_int;
+use mmap::{MapOption, MemoryMap};
+
+use crate::{
+    debugger::{self, Gregs},
+    tls,
+};
+
+/// What the child sends its parent when the program crashes
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Crash {
+    signal: u64,
+    /// For `SIGSEGV` and `SIGBUS`, the address accessed
+    fault_addr: u64,
+    gregs: Gregs,
+    fs_base: u64,
+}
+
+/// Write end of the pipe to the parent, in the child
+static REPORT_FD: AtomicI32 = AtomicI32::new(-1);
+
+const CRASH_SIGNALS: [c_int; 5] = [
+    libc::SIGSEGV,
+    libc::SIGBUS,
+    libc::SIGILL,
+    libc::SIGFPE,
+    libc::SIGABRT,
+];
+
+/// Large enough for `on_crash`, which must work when the program has
+/// overflowed its own stack
+const ALT_STACK_SIZE: usize = 64 * 1024;
+
+/// Runs `f` in a child process and waits for it, returning the status elk
+/// should exit with. If the child dies of a signal, says which on stderr,
+/// along with the registers of the crash if `catch_crashes` saw it.
+pub fn run(f: impl FnOnce() -> Result<(), Box<dyn Error>>) -> Result<i32, Box<dyn Error>> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let [read_fd, write_fd] = fds;
+    // or the child would print it again
+    io::stdout().flush()?;
+
+    let pid = unsafe { libc::fork() };
+    if pid == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+    if pid == 0 {
+        unsafe { libc::close(read_fd) };
+        REPORT_FD.store(write_fd, Ordering::SeqCst);
+        let code = match f() {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                1
+            }
+        };
+        std::process::exit(code);
+    }
+
+    unsafe { libc::close(write_fd) };
+    let mut pipe = unsafe { fs::File::from_raw_fd(read_fd) };
+    let mut buf = [0_u8; mem::size_of::<Crash>()];
+    // only ever written to on a crash, closed when the child exits
+    let crash = pipe
+        .read_exact(&mut buf)
+        .ok()
+        .map(|()| unsafe { ptr::read_unaligned(buf.as_ptr() as *const Crash) });
+
+    let mut status = 0;
+    while unsafe { libc::waitpid(pid, &mut status, 0) } == -1 {
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e.into());
+        }
+    }
+    if libc::WIFEXITED(status) {
+        return Ok(libc::WEXITSTATUS(status));
+    }
+    let signal = libc::WTERMSIG(status);
+    eprintln!("Killed by signal {} ({})", signal, signal_name(signal));
+    if let Some(crash) = crash.filter(|crash| crash.signal == signal as u64) {
+        if matches!(signal, libc::SIGSEGV | libc::SIGBUS) {
+            eprintln!("Fault address: {:?}", delf::Addr(crash.fault_addr));
+        }
+        eprintln!("{:?}", debugger::registers(&crash.gregs, crash.fs_base));
+    }
+    Ok(128 + signal)
+}
+
+fn signal_name(signal: c_int) -> String {
+    let name = unsafe { libc::strsignal(signal) };
+    if name.is_null() {
+        return "unknown".into();
+    }
+    unsafe { std::ffi::CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// In a child started by `run`, makes crashes of the program report its
+/// registers to the parent. Does nothing otherwise.
+pub fn catch_crashes() -> io::Result<()> {
+    if REPORT_FD.load(Ordering::SeqCst) < 0 {
+        return Ok(());
+    }
+    let stack = MemoryMap::new(
+        ALT_STACK_SIZE,
+        &[MapOption::MapReadable, MapOption::MapWritable],
+    )
+    .map_err(|e| io::Error::other(e.to_string()))?;
+    unsafe {
+        let alt_stack = libc::stack_t {
+            ss_sp: stack.data() as *mut libc::c_void,
+            ss_flags: 0,
+            ss_size: ALT_STACK_SIZE,
+        };
+        if libc::sigaltstack(&alt_stack, ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // in use until the program exits
+        mem::forget(stack);
+
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = on_crash as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK | libc::SA_RESETHAND;
+        for signal in CRASH_SIGNALS {
+            if libc::sigaction(signal, &action, ptr::null_mut()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs on the program's thread-locals: only system calls from here
+extern "C" fn on_crash(signal: c_int, info: *mut libc::siginfo_t, context: *mut libc::c_void) {
+    unsafe {
+        let crash = Crash {
+            signal: signal as u64,
+            fault_addr: (*info).si_addr() as u64,
+            gregs: (*(context as *mut libc::ucontext_t)).uc_mcontext.gregs,
+            fs_base: tls::get_fs().unwrap_or(0),
+        };
+        libc::write(
+            REPORT_FD.load(Ordering::SeqCst),
+            &crash as *const Crash as *const libc::c_void,
+            mem::size_of::<Crash>(),
+        );
+        // the default action is back, and happens once this returns
+        libc::syscall(libc::SYS_kill, libc::syscall(libc::SYS_getpid), signal);
+    }
+}
+
+// classic BPF, as seccomp filters are written in
+const BPF_LD_W_ABS: u16 = 0x20;
+const BPF_JMP_JEQ_K: u16 = 0x15;
+const BPF_JMP_JGE_K: u16 = 0x35;
+const BPF_JMP_JSET_K: u16 = 0x45;
+const BPF_RET_K: u16 = 0x06;
+
+const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+/// Offsets in `struct seccomp_data`
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+/// Lower half of the first argument
+const SECCOMP_DATA_ARG0: u32 = 16;
+/// Set in the numbers of x32 system calls, which x86-64 processes can make
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// Fail with `EPERM` under `--seccomp`: starting other programs, tracing
+/// and networking. Forking is denied too, see `restrict`.
+const DENIED_SYSCALLS: [libc::c_long; 14] = [
+    libc::SYS_execve,
+    libc::SYS_execveat,
+    libc::SYS_fork,
+    libc::SYS_vfork,
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_socket,
+    libc::SYS_socketpair,
+    libc::SYS_connect,
+    libc::SYS_bind,
+    libc::SYS_listen,
+    libc::SYS_accept,
+    libc::SYS_accept4,
+];
+
+fn bpf(code: u16, jt: u8, jf: u8, k: u32) -> libc::sock_filter {
+    libc::sock_filter { code, jt, jf, k }
+}
+
+/// Installs a seccomp filter denying `DENIED_SYSCALLS`, for elk and the
+/// program alike, and for good.
+///
+/// `clone`, which glibc's `fork` uses, is denied unless it starts a thread.
+/// `clone3` passes its flags in memory, which the filter cannot read: it
+/// fails with `ENOSYS`, which makes glibc fall back to `clone`.
+pub fn restrict() -> io::Result<()> {
+    let deny = |errno: c_int| bpf(BPF_RET_K, 0, 0, libc::SECCOMP_RET_ERRNO | errno as u32);
+    let mut filter = vec![
+        bpf(BPF_LD_W_ABS, 0, 0, SECCOMP_DATA_ARCH),
+        bpf(BPF_JMP_JEQ_K, 1, 0, AUDIT_ARCH_X86_64),
+        bpf(BPF_RET_K, 0, 0, SECCOMP_RET_KILL_PROCESS),
+        bpf(BPF_LD_W_ABS, 0, 0, SECCOMP_DATA_NR),
+        bpf(BPF_JMP_JGE_K, 0, 1, X32_SYSCALL_BIT),
+        bpf(BPF_RET_K, 0, 0, SECCOMP_RET_KILL_PROCESS),
+    ];
+    for nr in DENIED_SYSCALLS {
+        filter.push(bpf(BPF_JMP_JEQ_K, 0, 1, nr as u32));
+        filter.push(deny(libc::EPERM));
+    }
+    filter.extend([
+        bpf(BPF_JMP_JEQ_K, 0, 1, libc::SYS_clone3 as u32),
+        deny(libc::ENOSYS),
+        // the last check: the argument replaces the number
+        bpf(BPF_JMP_JEQ_K, 0, 3, libc::SYS_clone as u32),
+        bpf(BPF_LD_W_ABS, 0, 0, SECCOMP_DATA_ARG0),
+        bpf(BPF_JMP_JSET_K, 1, 0, libc::CLONE_THREAD as u32),
+        deny(libc::EPERM),
+        bpf(BPF_RET_K, 0, 0, libc::SECCOMP_RET_ALLOW),
+    ]);
+
+    let program = libc::sock_fprog {
+        len: filter.len() as u16,
+        filter: filter.as_mut_ptr(),
+    };
+    unsafe {
+        // needed to install a filter without privileges
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::prctl(
+            libc::PR_SET_SECCOMP,
+            libc::SECCOMP_MODE_FILTER,
+            &program as *const libc::sock_fprog,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use std::{ffi::OsString, fs, io, os::unix::ffi::OsStrExt};
+
+use delf::{AuxType, AuxvEntry};
+use mmap::{MapOption, MemoryMap};
+
+/// The stack a program starts with, as laid out by the kernel on exec
+/// (System V x86-64 ABI, section 3.4.1). From the stack pointer up:
+///
+///   - argc
+///   - argv pointers, null-terminated
+///   - envp pointers, null-terminated
+///   - auxiliary vector entries, terminated by `AT_NULL`
+///   - the strings and random bytes the above point to
+pub struct InitialStack {
+    /// Unmapped when dropped, so this must outlive the program
+    _map: MemoryMap,
+    /// Where `argc` is: what `rsp` must be when jumping to the entry point
+    pub pointer: u64,
+}
+
+impl InitialStack {
+    const SIZE: usize = 8 << 20;
+
+    /// `AT_RANDOM` and `AT_EXECFN` are added to `auxv`, since they point
+    /// into the stack
+    pub fn new(
+        args: &[OsString],
+        env: &[OsString],
+        auxv: &[AuxvEntry],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let map = MemoryMap::new(
+            Self::SIZE,
+            &[MapOption::MapReadable, MapOption::MapWritable],
+        )?;
+        let mut sp = map.data() as u64 + map.len() as u64;
+        let mut push = |bytes: &[u8]| {
+            sp -= bytes.len() as u64;
+            unsafe {
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), sp as *mut u8, bytes.len());
+            }
+            sp
+        };
+        let mut push_str = |s: &OsString| {
+            let mut bytes = s.as_bytes().to_vec();
+            bytes.push(0);
+            push(&bytes)
+        };
+
+        let execfn = args.first().map(&mut push_str).unwrap_or(0);
+        let argv = args.iter().map(&mut push_str).collect::<Vec<_>>();
+        let envp = env.iter().map(&mut push_str).collect::<Vec<_>>();
+        let random = push(&random_bytes()?);
+
+        let mut auxv = auxv
+            .iter()
+            .map(|e| (e.r#type as u64, e.value))
+            .collect::<Vec<_>>();
+        auxv.push((AuxType::Random as u64, random));
+        auxv.push((AuxType::ExecFn as u64, execfn));
+        auxv.push((AuxType::Null as u64, 0));
+
+        let mut words = vec![argv.len() as u64];
+        words.extend(argv);
+        words.push(0);
+        words.extend(envp);
+        words.push(0);
+        for (r#type, value) in auxv {
+            words.extend([r#type, value]);
+        }
+
+        // the stack pointer must be 16-byte aligned on entry
+        let mut pointer = (sp - (words.len() * 8) as u64) & !0xf;
+        let start = pointer;
+        for word in words {
+            unsafe {
+                (pointer as *mut u64).write(word);
+            }
+            pointer += 8;
+        }
+
+        Ok(Self {
+            _map: map,
+            pointer: start,
+        })
+    }
+}
+
+pub fn random_bytes() -> io::Result<[u8; 16]> {
+    use io::Read;
+    let mut res = [0; 16];
+    fs::File::open("/dev/urandom")?.read_exact(&mut res)?;
+    Ok(res)
+}
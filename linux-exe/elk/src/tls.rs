@@ -0,0 +1,198 @@
+use std::{
+    error::Error,
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        OnceLock,
+    },
+};
+
+use mmap::{MapOption, MemoryMap};
+
+use crate::{
+    process::{align_up, Process},
+    stack,
+};
+
+/// Static TLS layout, as on x86-64 (variant II): the thread pointer points
+/// to a thread control block, below which are the TLS blocks of each
+/// object, the executable's closest.
+#[derive(Default)]
+pub struct Layout {
+    /// Offset of the lowest block below the thread pointer
+    pub size: u64,
+    /// Strictest alignment of any block, and so of the thread pointer
+    pub align: u64,
+}
+
+impl Layout {
+    /// Makes room for the block described by a `Tls` segment, returning its
+    /// offset below the thread pointer
+    pub fn add(&mut self, ph: &delf::ProgramHeader) -> u64 {
+        let align = Some(ph.align.0)
+            .filter(|align| align.is_power_of_two())
+            .unwrap_or(1);
+        self.size = align_up(self.size + ph.memsz.0, align);
+        self.align = self.align.max(align);
+        self.size
+    }
+}
+
+/// Offsets of the TLS blocks below the thread pointer, by module ID (object
+/// index plus one), for `tls_get_addr`. Objects without TLS have none.
+static OFFSETS: OnceLock<Vec<Option<u64>>> = OnceLock::new();
+
+/// elk's own `fs` base, while the program's is installed
+static HOST_FS: AtomicU64 = AtomicU64::new(0);
+
+const ARCH_SET_FS: libc::c_int = 0x1002;
+const ARCH_GET_FS: libc::c_int = 0x1003;
+
+pub unsafe fn get_fs() -> io::Result<u64> {
+    let mut fs = 0_u64;
+    if libc::syscall(libc::SYS_arch_prctl, ARCH_GET_FS, &mut fs as *mut u64) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(fs)
+}
+
+unsafe fn set_fs(fs: u64) -> io::Result<()> {
+    if libc::syscall(libc::SYS_arch_prctl, ARCH_SET_FS, fs) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Runs `f` with elk's own thread-locals, for elk code called back by the
+/// program once `StaticTls::install` has been called.
+///
+/// # Safety
+///
+/// Nothing before this may touch thread-locals.
+pub unsafe fn with_host_tls<T>(f: impl FnOnce() -> T) -> T {
+    let guest = get_fs().expect("could not get the fs base");
+    set_fs(HOST_FS.load(Ordering::SeqCst)).expect("could not set the fs base");
+    let res = f();
+    set_fs(guest).expect("could not set the fs base");
+    res
+}
+
+/// TLS blocks and thread control block of the main thread
+pub struct StaticTls {
+    /// Unmapped when dropped, so this must outlive the program
+    _map: MemoryMap,
+    pub thread_pointer: u64,
+}
+
+impl StaticTls {
+    /// Large enough for glibc's `tcbhead_t` and musl's `struct pthread`
+    /// fields at fixed offsets, such as the stack guard at `fs:0x28`
+    const TCB_SIZE: u64 = 0x100;
+
+    /// Copies the TLS template of each object of `process` into its block,
+    /// once relocations are applied
+    pub fn new(process: &Process) -> Result<Self, Box<dyn Error>> {
+        let layout = &process.tls;
+        let page_size = region::page::size() as u64;
+        let align = layout.align.max(page_size);
+        let blocks = align_up(layout.size, align);
+        // mappings are only page-aligned, leave room to align further
+        let map = MemoryMap::new(
+            (align - page_size + blocks + Self::TCB_SIZE) as usize,
+            &[MapOption::MapReadable, MapOption::MapWritable],
+        )?;
+        let thread_pointer = align_up(map.data() as u64, align) + blocks;
+
+        for object in &process.objects {
+            let (offset, ph) = match (
+                object.tls_offset,
+                object.file.segment_of_type(delf::SegmentType::Tls),
+            ) {
+                (Some(offset), Some(ph)) => (offset, ph),
+                _ => continue,
+            };
+            // from memory rather than the file: the template can hold
+            // pointers, relocated with the rest of the object. The rest of
+            // the block (.tbss) is zero, like the mapping.
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    (object.base + ph.vaddr).0 as *const u8,
+                    (thread_pointer - offset) as *mut u8,
+                    ph.data.len(),
+                );
+            }
+        }
+
+        let random = stack::random_bytes()?;
+        let tcb = thread_pointer as *mut u64;
+        unsafe {
+            // the TCB points to itself, so that `fs:0` is the thread pointer
+            tcb.write(thread_pointer);
+            tcb.add(2).write(thread_pointer);
+            // stack guard and pointer guard
+            tcb.add(5)
+                .write(u64::from_le_bytes(random[..8].try_into()?));
+            tcb.add(6)
+                .write(u64::from_le_bytes(random[8..].try_into()?));
+        }
+
+        let offsets = process
+            .objects
+            .iter()
+            .map(|object| object.tls_offset)
+            .collect();
+        OFFSETS
+            .set(offsets)
+            .map_err(|_| "static TLS set up twice")?;
+
+        Ok(Self {
+            _map: map,
+            thread_pointer,
+        })
+    }
+
+    /// Points the `fs` segment at the thread pointer.
+    ///
+    /// # Safety
+    ///
+    /// elk's own thread-locals are lost from then on, except through
+    /// `with_host_tls`: this must be the last thing to do before jumping to
+    /// the program.
+    pub unsafe fn install(&self) -> io::Result<()> {
+        HOST_FS.store(get_fs()?, Ordering::SeqCst);
+        set_fs(self.thread_pointer)
+    }
+}
+
+/// Argument of `__tls_get_addr`, filled by `DTPMOD64` and `DTPOFF64`
+/// relocations
+#[repr(C)]
+pub struct TlsIndex {
+    module: u64,
+    offset: u64,
+}
+
+/// `__tls_get_addr`, called by code using the general dynamic TLS model.
+/// All blocks are static, so this only has to find them from the thread
+/// pointer. Module IDs of objects without TLS, or of no object at all,
+/// only come from memory the program corrupted: elk exits then, rather
+/// than unwind into the program.
+pub extern "C" fn tls_get_addr(index: &TlsIndex) -> *mut u8 {
+    let thread_pointer: u64;
+    unsafe {
+        std::arch::asm!("mov {}, fs:0", out(reg) thread_pointer);
+    }
+    let offset = OFFSETS.get().and_then(|offsets| {
+        let n = usize::try_from(index.module).ok()?.checked_sub(1)?;
+        *offsets.get(n)?
+    });
+    match offset {
+        Some(offset) => (thread_pointer - offset).wrapping_add(index.offset) as *mut u8,
+        None => unsafe {
+            with_host_tls(|| {
+                eprintln!("__tls_get_addr: no TLS block for module {}", index.module);
+                std::process::exit(1);
+            })
+        },
+    }
+}
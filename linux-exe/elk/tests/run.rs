@@ -0,0 +1,66 @@
+//! Runs the built `elk run` on the samples, and checks what the programs
+//! print and exit with
+
+use std::process::{Command, Output};
+
+fn elk_run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_elk"))
+        .arg("run")
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap()
+}
+
+fn assert_runs(args: &[&str], stdout: &str, status: i32) {
+    let output = elk_run(args);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        stdout,
+        "{}",
+        stderr
+    );
+    assert_eq!(output.status.code(), Some(status), "{}", stderr);
+}
+
+#[test]
+fn shared_library() {
+    let stdout = "hello from libgreet\nhello from libgreet\n";
+    assert_runs(&["samples/greet"], stdout, 20);
+    assert_runs(&["--bind-now", "samples/greet"], stdout, 20);
+}
+
+#[test]
+fn thread_locals() {
+    assert_runs(&["samples/tls"], "thread-locals ok\n", 43);
+}
+
+#[test]
+fn initializers_and_finalizers() {
+    assert_runs(
+        &["samples/initfini"],
+        "lib init\nexe init\nmain\nexe fini\nlib fini\n",
+        0,
+    );
+}
+
+#[test]
+fn overlapping_segments() {
+    assert_runs(&["samples/overlap"], "data!\n", 0);
+}
+
+#[test]
+fn glibc_program() {
+    assert_runs(&["samples/entrypoint"], "main is at 0x401040\n", 0);
+}
+
+#[test]
+fn sandboxed_crash() {
+    let output = elk_run(&["--sandbox", "samples/crash"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(128 + 11), "{}", stderr);
+    assert!(stderr.contains("Killed by signal 11"), "{}", stderr);
+    assert!(stderr.contains("Fault address: 00000000"), "{}", stderr);
+    assert!(stderr.contains("rip=0x401007"), "{}", stderr);
+}